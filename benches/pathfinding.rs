@@ -0,0 +1,48 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+use minesweeper_td::{
+    board::{Board, Coords, NumberRule, Topology},
+    pathfinding::{find_path, PathSearch},
+};
+
+// rows of towers with a gap at alternating ends
+// so the path has to snake back and forth across the board
+fn snake_board(size: usize) -> Board {
+    let mut board = Board::new(size, size, Topology::Square, NumberRule::Adjacent);
+    let size = size as i32;
+    for (i, y) in (2..size - 1).step_by(3).enumerate() {
+        let gap = if i % 2 == 0 { size - 1 } else { 0 };
+        for x in 0..size {
+            if x != gap {
+                board.try_place_tower(Coords::new(x, y)).unwrap();
+            }
+        }
+    }
+    board
+}
+
+fn bench_size(c: &mut Criterion, size: usize) {
+    let board = snake_board(size);
+    let start = Coords::new(0, 0);
+    let end = Coords::new(size as i32 - 1, size as i32 - 1);
+
+    let mut search = PathSearch::default();
+    c.bench_function(&format!("a* {0}x{0} reused", size), |b| {
+        b.iter(|| {
+            search
+                .find_path(black_box(&board), start, end)
+                .map(|path| path.len())
+        })
+    });
+    c.bench_function(&format!("a* {0}x{0} one off", size), |b| {
+        b.iter(|| find_path(black_box(&board), start, end).map(|path| path.len()))
+    });
+}
+
+fn pathfinding(c: &mut Criterion) {
+    bench_size(c, 21);
+    bench_size(c, 200);
+}
+
+criterion_group!(benches, pathfinding);
+criterion_main!(benches);
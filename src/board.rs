@@ -0,0 +1,1881 @@
+use rand::prelude::*;
+use std::collections::HashSet;
+
+use crate::{
+    castle::TerritoryInfo,
+    chunk::{self, Chunks},
+    graph::{self, GridGraph},
+    map::MapFile,
+    solver,
+};
+
+// how many times no guess mode re-rolls a ring before it starts taking mines out
+// square rings usually work out in a couple of tries, battlements almost never do
+const NO_GUESS_ATTEMPTS: usize = 5;
+// biggest bulge a noisy ring can have, in tiles
+// averaging keeps most of them under 1
+const NOISE_AMPLITUDE: f32 = 4.0;
+// how many of each terrain a generated board gets
+const TERRAIN_PATCHES: usize = 3;
+// how far past a ring an endless board has to exist
+// noise can push the ring out 4, then the numbers reach 2 more
+const RESERVE_MARGIN: i32 = 8;
+// an endless board starts about the size of the normal one
+const ENDLESS_START_RADIUS: i32 = 10;
+// enemies can dig through walls, but would rather walk on floor
+const WALL_PATH_COST: u32 = 4;
+
+// The minesweeper side of the game without any bevy.
+// Board owns the tile states, where the mines are and the numbers.
+// The systems in grid.rs only copy it onto the sprites
+// so the rules can be run (and tested) without an App.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Coords {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Coords {
+    pub fn new(x: i32, y: i32) -> Self {
+        Coords { x, y }
+    }
+
+    pub fn get_ring_coords(self, radius: i32) -> Vec<Coords> {
+        // radius of 0 is self
+        // 1 is 3x3
+        // 2 is 5x5, etc..
+        let mut v = Vec::new();
+        for i in -radius..=radius {
+            for j in -radius..=radius {
+                // only do the edges
+                if i == -radius || i == radius || j == -radius || j == radius {
+                    let x = self.x + i;
+                    let y = self.y + j;
+                    v.push(Coords::new(x, y));
+                }
+            }
+        }
+        v
+    }
+
+    pub fn get_hex_ring_coords(self, radius: i32) -> Vec<Coords> {
+        // radius of 0 is self
+        // 1 is the 6 around it
+        // 2 is the 12 around those, etc..
+        let mut v = Vec::new();
+        for j in -radius..=radius {
+            for i in -radius - 1..=radius + 1 {
+                let c = Coords::new(self.x + i, self.y + j);
+                if self.hex_distance(c) == radius {
+                    v.push(c);
+                }
+            }
+        }
+        v
+    }
+
+    pub fn get_knight_coords(self) -> Vec<Coords> {
+        [
+            (1, 2),
+            (2, 1),
+            (2, -1),
+            (1, -2),
+            (-1, -2),
+            (-2, -1),
+            (-2, 1),
+            (-1, 2),
+        ]
+        .iter()
+        .map(|(i, j)| Coords::new(self.x + i, self.y + j))
+        .collect()
+    }
+
+    // hexes 2 away that aren't in a straight line
+    // one step, then one step turned 60 degrees
+    pub fn get_hex_knight_coords(self) -> Vec<Coords> {
+        let step = |c: Coords, i: usize| {
+            let (dx, dy) = graph::neighbour_offsets(Topology::Hex, c)[i];
+            Coords::new(c.x + dx, c.y + dy)
+        };
+        let straight: Vec<Coords> = (0..6).map(|i| step(step(self, i), i)).collect();
+        self.get_hex_ring_coords(2)
+            .into_iter()
+            .filter(|c| !straight.contains(c))
+            .collect()
+    }
+
+    // steps between two hexes
+    pub fn hex_distance(self, other: Coords) -> i32 {
+        // shift each row back so the hexes line up on axes
+        // https://www.redblobgames.com/grids/hexagons/
+        let axial = |c: Coords| (c.x - (c.y - c.y.rem_euclid(2)) / 2, c.y);
+        let (q1, r1) = axial(self);
+        let (q2, r2) = axial(other);
+        let (dq, dr) = (q1 - q2, r1 - r2);
+        (dq.abs() + dr.abs() + (dq + dr).abs()) / 2
+    }
+}
+
+// how the tiles connect to each other
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Topology {
+    // 8 neighbours
+    Square,
+    // 6 neighbours
+    Hex,
+}
+
+impl Topology {
+    pub fn next(self) -> Self {
+        match self {
+            Topology::Square => Topology::Hex,
+            Topology::Hex => Topology::Square,
+        }
+    }
+
+    pub fn ring(self, coords: Coords, radius: i32) -> Vec<Coords> {
+        match self {
+            Topology::Square => coords.get_ring_coords(radius),
+            Topology::Hex => coords.get_hex_ring_coords(radius),
+        }
+    }
+
+    // which ring b is on around a
+    pub fn distance(self, a: Coords, b: Coords) -> i32 {
+        match self {
+            Topology::Square => (a.x - b.x).abs().max((a.y - b.y).abs()),
+            Topology::Hex => a.hex_distance(b),
+        }
+    }
+
+    // how far right this row is drawn, in tiles
+    pub fn row_shift(self, y: i32) -> f32 {
+        match self {
+            Topology::Hex if y.rem_euclid(2) == 1 => 0.5,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PlaceError {
+    TowerAlready,
+    Floor,
+    Flagged,
+    Blocked,
+    OffBoard,
+    //NotEdge,
+}
+
+// which tiles a number counts
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NumberRule {
+    // the tiles next to it
+    Adjacent,
+    // a knight's move away
+    KnightsMove,
+    // only the ring 2 out. On square boards that's the edge of the 5x5
+    Ring5x5,
+    // the tiles next to it, but a mine tile can hold 2 mines
+    DoubleMines,
+}
+
+impl NumberRule {
+    pub fn next(self) -> Self {
+        match self {
+            NumberRule::Adjacent => NumberRule::KnightsMove,
+            NumberRule::KnightsMove => NumberRule::Ring5x5,
+            NumberRule::Ring5x5 => NumberRule::DoubleMines,
+            NumberRule::DoubleMines => NumberRule::Adjacent,
+        }
+    }
+
+    // most mines one tile can hold
+    pub fn max_mines(self) -> usize {
+        match self {
+            NumberRule::DoubleMines => 2,
+            _ => 1,
+        }
+    }
+}
+
+// the outline of each ring of territory
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RingShape {
+    // squares, or hexagons on a hex board
+    Square,
+    Diamond,
+    Circle,
+    // a circle with bumps
+    Noise,
+}
+
+impl RingShape {
+    pub fn next(self) -> Self {
+        match self {
+            RingShape::Square => RingShape::Diamond,
+            RingShape::Diamond => RingShape::Circle,
+            RingShape::Circle => RingShape::Noise,
+            RingShape::Noise => RingShape::Square,
+        }
+    }
+}
+
+// how the territory grows when a number is filled
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ExpansionRule {
+    // every wall in the next ring
+    Square,
+    // the next ring is every other tile, numbers and walls
+    Battlements,
+    // only around the filled number, cascading through 0s
+    // mines are decided as the walls next to them get uncovered
+    FloodFill,
+}
+
+impl ExpansionRule {
+    pub fn next(self) -> Self {
+        match self {
+            ExpansionRule::Square => ExpansionRule::Battlements,
+            ExpansionRule::Battlements => ExpansionRule::FloodFill,
+            ExpansionRule::FloodFill => ExpansionRule::Square,
+        }
+    }
+}
+
+// a number that can't be right anymore
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Contradiction {
+    // more towers around it than the number
+    TooManyTowers,
+    // not enough walls left to build the rest on
+    NotEnoughWalls,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TileState {
+    Wall,
+    // a wall the player thinks has a mine
+    Flagged,
+    Floor,
+    Tower,
+    // water. Never revealed, never built on
+    Blocked,
+}
+
+// what the ground is like under a tile
+// it stays the same when a wall is cleared
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Terrain {
+    Plain,
+    // can't be walked through or built on. Never has a mine
+    Water,
+    // slows enemies
+    Rock,
+    // towers here reach further
+    HighGround,
+    // slows enemies a lot. towers here don't reach as far
+    Swamp,
+}
+
+impl Terrain {
+    // how long it takes to walk across
+    // None can't be walked on
+    pub fn movement_cost(self) -> Option<u32> {
+        match self {
+            Terrain::Plain | Terrain::HighGround => Some(1),
+            Terrain::Rock => Some(2),
+            Terrain::Swamp => Some(3),
+            Terrain::Water => None,
+        }
+    }
+
+    // multiplies the range of a tower built here
+    pub fn range_bonus(self) -> f32 {
+        match self {
+            Terrain::HighGround => 1.25,
+            Terrain::Swamp => 0.8,
+            _ => 1.0,
+        }
+    }
+}
+
+impl TileState {
+    // flags are only a note from the player
+    // for the rules they're still walls
+    pub fn is_wall(&self) -> bool {
+        matches!(self, TileState::Wall | TileState::Flagged)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BoardTile {
+    pub state: TileState,
+    // mines next to this floor. Doesn't go down when towers are built
+    pub number: usize,
+    // towers built next to it since the number showed up
+    // a tower on a double counts 2
+    pub towers: usize,
+    // how many mines are under those towers
+    pub found: usize,
+    pub mine: bool,
+    // holds 2 mines. Only with NumberRule::DoubleMines
+    pub double: bool,
+    // whether there's a mine here is settled
+    // a chord or flood fill rolled it, or a map said so
+    // floor is always decided
+    pub decided: bool,
+    // floor that was given a number by generation
+    // cleared floor shows 0 too, but doesn't tell you anything
+    pub clue: bool,
+    // random roll for a noisy ring. Board::wobble smooths it
+    wobble: f32,
+    pub terrain: Terrain,
+}
+
+impl BoardTile {
+    fn new() -> Self {
+        BoardTile {
+            state: TileState::Wall,
+            number: 0,
+            towers: 0,
+            found: 0,
+            mine: false,
+            double: false,
+            decided: false,
+            clue: false,
+            wobble: 0.0,
+            terrain: Terrain::Plain,
+        }
+    }
+
+    fn make_floor(&mut self) {
+        // anything left under a wall that gets cleared is gone
+        self.state = TileState::Floor;
+        self.mine = false;
+        self.double = false;
+        self.decided = true;
+        self.clear_number();
+    }
+
+    fn clear_number(&mut self) {
+        self.number = 0;
+        self.towers = 0;
+        self.found = 0;
+        self.clue = false;
+    }
+
+    pub fn mines(&self) -> usize {
+        match (self.mine, self.double) {
+            (false, _) => 0,
+            (true, false) => 1,
+            (true, true) => 2,
+        }
+    }
+
+    // what's left to build. This is what's drawn on the tile
+    pub fn remaining(&self) -> usize {
+        self.number.saturating_sub(self.towers)
+    }
+}
+
+#[derive(Clone)]
+pub struct Board {
+    // width and height. None goes on forever
+    bounds: Option<(usize, usize)>,
+    topology: Topology,
+    number_rule: NumberRule,
+    tiles: Chunks<BoardTile>,
+    // walls rolled as they're uncovered each get their own rng from this
+    seed: u64,
+    // tiles that might walk differently now, oldest first
+    cost_changes: Vec<Coords>,
+}
+
+impl Board {
+    pub fn new(width: usize, height: usize, topology: Topology, number_rule: NumberRule) -> Self {
+        let mut tiles = Chunks::new(BoardTile::new());
+        let min = chunk::chunk_of(Coords::new(0, 0));
+        let max = chunk::chunk_of(Coords::new(width as i32 - 1, height as i32 - 1));
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                tiles.insert_chunk(Coords::new(x, y));
+            }
+        }
+        Board {
+            bounds: Some((width, height)),
+            topology,
+            number_rule,
+            tiles,
+            seed: 0,
+            cost_changes: Vec::new(),
+        }
+    }
+
+    // chording and flood fill roll from this
+    // so the board doesn't depend on what order tiles are uncovered in
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    // no edges. Chunks are made as the territory reaches them
+    pub fn endless(center: Coords, topology: Topology, number_rule: NumberRule) -> Self {
+        let mut tiles = Chunks::new(BoardTile::new());
+        for c in chunk::chunks_around(center, ENDLESS_START_RADIUS) {
+            tiles.insert_chunk(c);
+        }
+        Board {
+            bounds: None,
+            topology,
+            number_rule,
+            tiles,
+            seed: 0,
+            cost_changes: Vec::new(),
+        }
+    }
+
+    // the tiles a number at coords counts
+    // always goes both ways. If a counts b, b counts a
+    pub fn number_area(&self, coords: Coords) -> Vec<Coords> {
+        match (self.number_rule, self.topology) {
+            (NumberRule::Adjacent | NumberRule::DoubleMines, _) => {
+                self.neighbours(coords).collect()
+            }
+            (NumberRule::KnightsMove, Topology::Square) => coords.get_knight_coords(),
+            (NumberRule::KnightsMove, Topology::Hex) => coords.get_hex_knight_coords(),
+            (NumberRule::Ring5x5, _) => self.ring(coords, 2),
+        }
+    }
+
+    pub fn max_mines(&self) -> usize {
+        self.number_rule.max_mines()
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    // chunks hang over the edge of a bounded board
+    fn in_bounds(&self, coords: Coords) -> bool {
+        match self.bounds {
+            Some((width, height)) => {
+                coords.x >= 0
+                    && coords.y >= 0
+                    && (coords.x as usize) < width
+                    && (coords.y as usize) < height
+            }
+            None => true,
+        }
+    }
+
+    // a small board from rows of text, top row first
+    // # wall, * mine, . floor with a number, C floor without one
+    #[cfg(test)]
+    pub(crate) fn from_rows(rows: &[&str]) -> Board {
+        let height = rows.len();
+        let mut board = Board::new(
+            rows[0].len(),
+            height,
+            Topology::Square,
+            NumberRule::Adjacent,
+        );
+        for (row, line) in rows.iter().enumerate() {
+            let y = (height - 1 - row) as i32;
+            for (x, symbol) in line.chars().enumerate() {
+                let c = Coords::new(x as i32, y);
+                match symbol {
+                    '*' => board.get_mut(c).unwrap().mine = true,
+                    '.' | 'C' => {
+                        board.reveal(c);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (row, line) in rows.iter().enumerate() {
+            let y = (height - 1 - row) as i32;
+            for (x, symbol) in line.chars().enumerate() {
+                if symbol != '.' {
+                    continue;
+                }
+                let c = Coords::new(x as i32, y);
+                let number = board
+                    .number_area(c)
+                    .into_iter()
+                    .filter(|&n| matches!(board.get(n), Some(t) if t.mine))
+                    .count();
+                let tile = board.get_mut(c).unwrap();
+                tile.number = number;
+                tile.clue = true;
+            }
+        }
+        board
+    }
+
+    pub fn get(&self, coords: Coords) -> Option<&BoardTile> {
+        if !self.in_bounds(coords) {
+            return None;
+        }
+        self.tiles.get(coords)
+    }
+
+    fn get_mut(&mut self, coords: Coords) -> Option<&mut BoardTile> {
+        if !self.in_bounds(coords) {
+            return None;
+        }
+        self.tiles.get_mut(coords)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Coords, &BoardTile)> + '_ {
+        self.tiles.iter().filter(|(c, _)| self.in_bounds(*c))
+    }
+
+    pub fn is_endless(&self) -> bool {
+        self.bounds.is_none()
+    }
+
+    // the chunks that have been made so far
+    pub fn chunks(&self) -> impl Iterator<Item = Coords> + '_ {
+        self.tiles.chunks()
+    }
+
+    // makes sure the chunks around center exist on an endless board
+    // new tiles get their noise rolled if the rings need it
+    fn reserve<R: Rng>(&mut self, center: Coords, radius: i32, shape: RingShape, rng: &mut R) {
+        if self.bounds.is_some() {
+            return;
+        }
+        for c in chunk::chunks_around(center, radius) {
+            if self.tiles.insert_chunk(c) && shape == RingShape::Noise {
+                for t in chunk::chunk_tiles(c) {
+                    if let Some(tile) = self.tiles.get_mut(t) {
+                        tile.wobble = rng.gen_range(-1.0..1.0);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn state(&self, coords: Coords) -> Option<TileState> {
+        self.get(coords).map(|t| t.state)
+    }
+
+    // a tower on a tile without a mine
+    // the board remembers where the mines really were
+    pub fn is_misplaced(&self, coords: Coords) -> bool {
+        matches!(self.get(coords), Some(t) if t.state == TileState::Tower && !t.mine)
+    }
+
+    pub fn remaining(&self, coords: Coords) -> usize {
+        self.get(coords).map_or(0, |t| t.remaining())
+    }
+
+    pub fn contradiction(&self, coords: Coords) -> Option<Contradiction> {
+        let tile = self.get(coords)?;
+        if tile.state != TileState::Floor || !tile.clue {
+            return None;
+        }
+        if tile.towers > tile.number {
+            return Some(Contradiction::TooManyTowers);
+        }
+        let walls = self
+            .number_area(coords)
+            .into_iter()
+            .filter(|&c| matches!(self.state(c), Some(s) if s.is_wall()))
+            .count();
+        if tile.remaining() > walls * self.max_mines() {
+            return Some(Contradiction::NotEnoughWalls);
+        }
+        None
+    }
+
+    // turn a wall into floor
+    // returns false if there was nothing to reveal
+    pub fn reveal(&mut self, coords: Coords) -> bool {
+        if let Some(tile) = self.get_mut(coords) {
+            if tile.state.is_wall() {
+                tile.make_floor();
+                self.cost_changes.push(coords);
+                return true;
+            }
+        }
+        false
+    }
+
+    // flag or unflag a wall
+    // returns false if the tile can't have a flag
+    pub fn toggle_flag(&mut self, coords: Coords) -> bool {
+        if let Some(tile) = self.get_mut(coords) {
+            match tile.state {
+                TileState::Wall => tile.state = TileState::Flagged,
+                TileState::Flagged => tile.state = TileState::Wall,
+                _ => return false,
+            }
+            return true;
+        }
+        false
+    }
+
+    // towers go next to the territory
+    // or anywhere a number can see, for numbers that count further out
+    pub fn can_build(&self, coords: Coords) -> bool {
+        let next_to_floor = self
+            .neighbours(coords)
+            .any(|c| self.state(c) == Some(TileState::Floor));
+        next_to_floor
+            || self
+                .number_area(coords)
+                .into_iter()
+                .any(|c| matches!(self.get(c), Some(t) if t.state == TileState::Floor && t.clue))
+    }
+
+    pub fn try_place_tower(&mut self, coords: Coords) -> Result<(), PlaceError> {
+        let tile = self.get_mut(coords).ok_or(PlaceError::OffBoard)?;
+        match tile.state {
+            TileState::Wall => {
+                tile.state = TileState::Tower;
+                self.cost_changes.push(coords);
+                Ok(())
+            }
+            TileState::Tower => Err(PlaceError::TowerAlready),
+            // unflag it first
+            // stops a stray click building on a flag
+            TileState::Flagged => Err(PlaceError::Flagged),
+            TileState::Blocked => Err(PlaceError::Blocked),
+            _ => Err(PlaceError::Floor),
+        }
+    }
+
+    // a number with a tower on each of its mines
+    // every wall left around it has to be empty
+    pub fn is_satisfied(&self, coords: Coords) -> bool {
+        matches!(self.get(coords), Some(t) if t.state == TileState::Floor
+            && t.clue
+            && t.towers == t.number
+            && t.found == t.towers)
+    }
+
+    // chording
+    // clears the walls around a satisfied number
+    // and gives each of them its own number
+    // returns the tiles that were cleared
+    pub fn chord(&mut self, coords: Coords, bombs_percent: f32) -> Vec<Coords> {
+        if !self.is_satisfied(coords) {
+            return Vec::new();
+        }
+
+        // flags are left alone
+        let walls: Vec<Coords> = self
+            .number_area(coords)
+            .into_iter()
+            .filter(|&c| self.state(c) == Some(TileState::Wall))
+            .collect();
+        for &c in walls.iter() {
+            self.reveal(c);
+        }
+        // after all of them are floor so they don't count each other as walls
+        // walls past the territory haven't been rolled yet
+        for &c in walls.iter() {
+            self.uncover(c, bombs_percent);
+        }
+        walls
+    }
+
+    // flood fill out from a number
+    // like chording, but anything that comes up satisfied (a 0) is chorded as well
+    // returns the tiles that were cleared
+    pub fn flood(&mut self, origin: Coords, bombs_percent: f32) -> Vec<Coords> {
+        let mut cleared = Vec::new();
+        let mut open = vec![origin];
+        while let Some(c) = open.pop() {
+            if !self.is_satisfied(c) {
+                continue;
+            }
+            // every wall a number counts is already decided
+            // and this number is satisfied, so none of these are mines
+            let walls: Vec<Coords> = self
+                .number_area(c)
+                .into_iter()
+                .filter(|&w| self.state(w) == Some(TileState::Wall))
+                .collect();
+            for &w in walls.iter() {
+                self.reveal(w);
+            }
+            for w in walls {
+                self.uncover(w, bombs_percent);
+                cleared.push(w);
+                open.push(w);
+            }
+        }
+        cleared
+    }
+
+    // the first 3x3 is always safe
+    fn open(&mut self, center: Coords, bombs_percent: f32) {
+        self.reveal(center);
+        let ring = self.ring(center, 1);
+        for &c in ring.iter() {
+            self.reveal(c);
+        }
+        for &c in ring.iter() {
+            self.uncover(c, bombs_percent);
+        }
+        for c in ring {
+            self.flood(c, bombs_percent);
+        }
+    }
+
+    // roll for mines around a new floor tile, then number it
+    // each wall rolls from its own rng, so it comes out the same
+    // whichever number uncovers it and whenever that happens
+    fn uncover(&mut self, coords: Coords, bombs_percent: f32) {
+        // flood fill can run off the chunks that are there
+        // square reserves don't roll anything
+        self.reserve(
+            coords,
+            RESERVE_MARGIN,
+            RingShape::Square,
+            &mut self.tile_rng(coords),
+        );
+        for c in self.number_area(coords) {
+            let undecided = matches!(self.get(c), Some(t) if !t.decided && t.state.is_wall());
+            if undecided {
+                let mut rng = self.tile_rng(c);
+                if rng.gen::<f32>() < bombs_percent {
+                    self.place_mine(c, &mut rng);
+                }
+                if let Some(tile) = self.get_mut(c) {
+                    tile.decided = true;
+                }
+            }
+        }
+        self.set_number(coords);
+    }
+
+    fn tile_rng(&self, coords: Coords) -> StdRng {
+        StdRng::seed_from_u64(tile_seed(self.seed, coords))
+    }
+
+    fn place_mine<R: Rng>(&mut self, coords: Coords, rng: &mut R) {
+        let double = self.number_rule == NumberRule::DoubleMines && rng.gen_bool(0.5);
+        if let Some(tile) = self.get_mut(coords) {
+            tile.mine = true;
+            tile.double = double;
+        }
+    }
+
+    // furthest floor from center, in rings
+    pub fn reach(&self, center: Coords) -> i32 {
+        self.iter()
+            .filter(|(_, tile)| tile.state == TileState::Floor)
+            .map(|(c, _)| self.topology.distance(center, c))
+            .max()
+            .unwrap_or(0)
+    }
+
+    // count everything around a floor tile that was just uncovered
+    fn set_number(&mut self, coords: Coords) {
+        let mut number = 0;
+        let mut towers = 0;
+        let mut found = 0;
+        for c in self.number_area(coords) {
+            if let Some(tile) = self.get(c) {
+                number += tile.mines();
+                if tile.state == TileState::Tower {
+                    towers += tile.mines().max(1);
+                    found += tile.mines();
+                }
+            }
+        }
+        if let Some(tile) = self.get_mut(coords) {
+            tile.number = number;
+            tile.towers = towers;
+            tile.found = found;
+            tile.clue = true;
+        }
+    }
+
+    // a tower counts towards every number around it
+    // returns the numbers that now have a tower on every mine
+    pub fn count_tower(&mut self, coords: Coords) -> Vec<Coords> {
+        let mines = self.get(coords).map_or(0, |t| t.mines());
+        let mut filled = Vec::new();
+        for c in self.number_area(coords) {
+            if let Some(tile) = self.get_mut(c) {
+                if tile.state == TileState::Floor && tile.clue {
+                    // a guess counts as 1
+                    tile.towers += mines.max(1);
+                    if mines > 0 {
+                        tile.found += mines;
+
+                        // only count as filled if the tower being placed
+                        // caused this to fill
+                        // a guess next to it can show 0 without filling it
+                        if tile.found == tile.number {
+                            filled.push(c);
+                        }
+                    }
+                }
+            }
+        }
+        filled
+    }
+
+    // grow the territory out to territory.radius
+    // clears the old ring, places the new mines and numbers
+    // every mine is drawn from rng so the same seed gives the same board
+    // with no_guess on, the mines are re-rolled until the solver
+    // can prove every mine the new numbers count
+    // returns false if no_guess had to take mines out to get there
+    pub fn expand<R: Rng>(&mut self, territory: &TerritoryInfo, rng: &mut R) -> bool {
+        if territory.expansion == ExpansionRule::FloodFill && territory.radius > 1 {
+            // after the first 3x3, flood fill grows from flood()
+            return true;
+        }
+
+        if !territory.no_guess {
+            self.expand_once(territory, rng);
+            return true;
+        }
+
+        let before = self.clone();
+        for _ in 0..NO_GUESS_ATTEMPTS {
+            self.expand_once(territory, rng);
+            if solver::is_solvable(self) {
+                return true;
+            }
+            *self = before.clone();
+        }
+        // battlements numbers see too many walls to pin down a random ring
+        // so the mines that can't be proved are taken back out one at a time
+        // the ring ends up with fewer mines than bombs_percent asked for
+        // a map's mines can go too
+        self.expand_once(territory, rng);
+        loop {
+            let unproven = solver::unproven_mines(self);
+            if unproven.is_empty() {
+                return false;
+            }
+            let c = unproven[rng.gen_range(0..unproven.len())];
+            self.remove_mine(c);
+        }
+    }
+
+    fn expand_once<R: Rng>(&mut self, territory: &TerritoryInfo, rng: &mut R) {
+        let center = Coords::new(territory.x as i32, territory.y as i32);
+        let radius = territory.radius;
+        let shape = territory.shape;
+
+        self.reserve(center, radius + RESERVE_MARGIN, shape, rng);
+        if radius == 1 && shape == RingShape::Noise {
+            self.roll_wobble(rng);
+        }
+        if radius == 1 {
+            // set center to floor
+            self.reveal(center);
+            if let Some(tile) = self.get_mut(center) {
+                tile.clear_number();
+            }
+        }
+        // radius goes up += 2
+        if radius > 2 && territory.expansion == ExpansionRule::Square {
+            // clear current numbers
+            // and the ones chording gave the walls outside them
+            let mut old = self.shape_ring(center, radius - 2, shape);
+            old.append(&mut self.shape_ring(center, radius - 1, shape));
+            for c in old {
+                if let Some(tile) = self.get_mut(c) {
+                    if tile.state == TileState::Floor {
+                        tile.clear_number();
+                    }
+                }
+            }
+            // clear old walls
+            for c in self.shape_ring(center, radius - 1, shape) {
+                self.reveal(c);
+            }
+        }
+
+        match territory.expansion {
+            ExpansionRule::Battlements => {
+                self.expand_battlements(center, radius, shape, territory.bombs_percent, rng)
+            }
+            ExpansionRule::Square => {
+                self.expand_square(center, radius, shape, territory.bombs_percent, rng)
+            }
+            ExpansionRule::FloodFill => self.open(center, territory.bombs_percent),
+        }
+    }
+
+    // every tile whose shape_distance from center is radius
+    // each tile is in exactly one ring, so rings never overlap or leave gaps
+    fn shape_ring(&self, center: Coords, radius: i32, shape: RingShape) -> Vec<Coords> {
+        if shape == RingShape::Square {
+            return self.ring(center, radius);
+        }
+        self.iter()
+            .map(|(c, _)| c)
+            .filter(|&c| self.shape_distance(center, c, shape) == radius)
+            .collect()
+    }
+
+    // inside the rings opened so far
+    pub fn in_territory(&self, territory: &TerritoryInfo, coords: Coords) -> bool {
+        let center = Coords::new(territory.x as i32, territory.y as i32);
+        self.shape_distance(center, coords, territory.shape) <= territory.radius
+    }
+
+    fn shape_distance(&self, center: Coords, coords: Coords, shape: RingShape) -> i32 {
+        if coords == center {
+            return 0;
+        }
+        // measured where the tiles are drawn, so hex rows line up
+        let dx = (coords.x as f32 + self.topology.row_shift(coords.y))
+            - (center.x as f32 + self.topology.row_shift(center.y));
+        let dy = (coords.y - center.y) as f32;
+        let circle = (dx * dx + dy * dy).sqrt();
+        let distance = match shape {
+            RingShape::Square => return self.topology.distance(center, coords),
+            RingShape::Diamond => dx.abs() + dy.abs(),
+            RingShape::Circle => circle,
+            RingShape::Noise => {
+                // no bumps right by the castle so the start looks the same
+                circle + self.wobble(coords) * (circle / 4.0).min(1.0)
+            }
+        };
+        // the castle's neighbours are always the first ring
+        (distance.round() as i32).max(1)
+    }
+
+    // random bumps for RingShape::Noise
+    fn roll_wobble<R: Rng>(&mut self, rng: &mut R) {
+        let coords: Vec<Coords> = self.iter().map(|(c, _)| c).collect();
+        for c in coords {
+            if let Some(tile) = self.get_mut(c) {
+                tile.wobble = rng.gen_range(-1.0..1.0);
+            }
+        }
+    }
+
+    // each tile is averaged with its neighbours so the bumps are hills, not static
+    fn wobble(&self, coords: Coords) -> f32 {
+        let around: Vec<f32> = std::iter::once(coords)
+            .chain(self.neighbours(coords))
+            .filter_map(|n| self.get(n))
+            .map(|t| t.wobble)
+            .collect();
+        if around.is_empty() {
+            return 0.0;
+        }
+        around.iter().sum::<f32>() / around.len() as f32 * NOISE_AMPLITUDE
+    }
+
+    fn expand_battlements<R: Rng>(
+        &mut self,
+        center: Coords,
+        radius: i32,
+        shape: RingShape,
+        bombs_percent: f32,
+        rng: &mut R,
+    ) {
+        // radius starts at 1 for the 3x3
+        let mut wall_set: HashSet<Coords> = HashSet::new();
+        let mut floor_set: HashSet<Coords> = HashSet::new();
+        if radius > 2 {
+            // clear old
+            let mut old_inner = self.shape_ring(center, radius - 2, shape);
+            let mut old_outer = self.shape_ring(center, radius - 1, shape);
+
+            // only inner will have a number, but not worth separating for that
+            old_inner.append(&mut old_outer);
+
+            // skip towers
+            // set walls to floor
+            // number to 0
+            for c in old_inner {
+                if let Some(tile) = self.get_mut(c) {
+                    if !matches!(tile.state, TileState::Tower | TileState::Blocked) {
+                        tile.make_floor();
+                        self.cost_changes.push(c);
+                    }
+                }
+            }
+        }
+
+        // mines can only go on the board
+        // and only in walls
+        for c in self.shape_ring(center, radius, shape) {
+            if !matches!(self.state(c), Some(s) if s.is_wall()) {
+                continue;
+            }
+            // every tile on a diamond ring has the same x + y
+            let floor = match shape {
+                RingShape::Diamond => c.x % 2 == 0,
+                _ => (c.x + c.y) % 2 == 0,
+            };
+            if floor {
+                floor_set.insert(c);
+            } else {
+                wall_set.insert(c);
+            }
+        }
+        for c in self.shape_ring(center, radius + 1, shape) {
+            if matches!(self.state(c), Some(s) if s.is_wall()) {
+                wall_set.insert(c);
+            }
+        }
+        // knight's move and 5x5 numbers reach past the next ring
+        for &f in floor_set.iter() {
+            for c in self.number_area(f) {
+                if !floor_set.contains(&c) && matches!(self.state(c), Some(s) if s.is_wall()) {
+                    wall_set.insert(c);
+                }
+            }
+        }
+
+        let (mut mine_set, open) = self.split_decided(wall_set);
+        self.decide(open.iter().copied());
+        if !open.is_empty() {
+            // battlements has always placed one more than the percent
+            let number_total = open.len() as f32 * bombs_percent;
+            let picked = pick_mines(open, number_total.floor() as usize + 1, rng);
+            let mut picked: Vec<Coords> = picked.into_iter().collect();
+            // sorted so doubles are rolled in the same order every run
+            picked.sort();
+            for &c in picked.iter() {
+                self.place_mine(c, rng);
+            }
+            mine_set.append(&mut picked);
+        }
+
+        // calculate numbers
+        // and set to floor
+        for floor in floor_set {
+            let number = self
+                .number_area(floor)
+                .into_iter()
+                .filter(|c| mine_set.contains(c))
+                .filter_map(|c| self.get(c))
+                .map(|t| t.mines())
+                .sum();
+            if let Some(tile) = self.get_mut(floor) {
+                tile.make_floor();
+                tile.number = number;
+                tile.clue = true;
+                self.cost_changes.push(floor);
+            }
+        }
+    }
+
+    fn expand_square<R: Rng>(
+        &mut self,
+        center: Coords,
+        radius: i32,
+        shape: RingShape,
+        bombs_percent: f32,
+        rng: &mut R,
+    ) {
+        // set all to floor
+        let ring = self.shape_ring(center, radius, shape);
+        for &c in ring.iter() {
+            if let Some(tile) = self.get_mut(c) {
+                if !matches!(tile.state, TileState::Tower | TileState::Blocked) {
+                    tile.make_floor();
+                    tile.clue = true;
+                    self.cost_changes.push(c);
+                }
+            }
+        }
+
+        // get a set of all the walls in neighbours of the neighbours
+        let mut wall_set: HashSet<Coords> = HashSet::new();
+        for &c in ring.iter() {
+            if self.get(c).is_some() {
+                for wc in self.number_area(c) {
+                    if matches!(self.state(wc), Some(s) if s.is_wall()) {
+                        wall_set.insert(wc);
+                    }
+                }
+            }
+        }
+
+        let (fixed, open) = self.split_decided(wall_set);
+        self.decide(open.iter().copied());
+        let number_total = open.len() as f32 * bombs_percent;
+        let mut mines: Vec<Coords> = pick_mines(open, number_total.floor() as usize, rng)
+            .into_iter()
+            .collect();
+        mines.sort();
+        // for each bomb, check the tiles that count it
+        // if it's a number, increment its count
+        for c in mines {
+            self.place_mine(c, rng);
+            let weight = self.get(c).map_or(0, |t| t.mines());
+            for n in self.number_area(c) {
+                if let Some(tile) = self.get_mut(n) {
+                    if tile.state == TileState::Floor && tile.clue {
+                        tile.number += weight;
+                    }
+                }
+            }
+        }
+        // older numbers already count the mines a map put down
+        // only the new ring needs them
+        for c in fixed {
+            let weight = self.get(c).map_or(0, |t| t.mines());
+            for n in self.number_area(c) {
+                if !ring.contains(&n) {
+                    continue;
+                }
+                if let Some(tile) = self.get_mut(n) {
+                    if tile.state == TileState::Floor && tile.clue {
+                        tile.number += weight;
+                    }
+                }
+            }
+        }
+    }
+
+    // walls a map decided keep whatever it gave them
+    // returns the ones with mines, and the walls still open for new mines
+    fn split_decided(&self, walls: HashSet<Coords>) -> (Vec<Coords>, HashSet<Coords>) {
+        let (decided, open): (HashSet<Coords>, HashSet<Coords>) = walls
+            .into_iter()
+            .partition(|&c| matches!(self.get(c), Some(t) if t.decided));
+        let mut mines: Vec<Coords> = decided
+            .into_iter()
+            .filter(|&c| matches!(self.get(c), Some(t) if t.mine))
+            .collect();
+        mines.sort();
+        (mines, open)
+    }
+
+    pub fn terrain(&self, coords: Coords) -> Terrain {
+        self.get(coords).map_or(Terrain::Plain, |t| t.terrain)
+    }
+
+    // off the board is open ground
+    pub fn movement_cost(&self, coords: Coords) -> Option<u32> {
+        self.terrain(coords).movement_cost()
+    }
+
+    // what it costs an enemy to step onto a tile
+    // None for towers, water and off the board
+    pub fn path_cost(&self, coords: Coords) -> Option<u32> {
+        let tile = self.get(coords)?;
+        let cost = tile.terrain.movement_cost()?;
+        match tile.state {
+            TileState::Floor => Some(cost),
+            TileState::Wall | TileState::Flagged => Some(cost * WALL_PATH_COST),
+            TileState::Tower | TileState::Blocked => None,
+        }
+    }
+
+    pub fn set_terrain(&mut self, coords: Coords, terrain: Terrain) {
+        if let Some(tile) = self.get_mut(coords) {
+            tile.terrain = terrain;
+            if terrain == Terrain::Water {
+                tile.state = TileState::Blocked;
+                tile.mine = false;
+                tile.decided = true;
+            } else if tile.state == TileState::Blocked {
+                // was water
+                tile.state = TileState::Wall;
+                tile.decided = false;
+            }
+            self.cost_changes.push(coords);
+        }
+    }
+
+    // a few patches of each terrain for a generated board
+    // the castle's first 5x5 stays plain so the start is always the same
+    pub fn scatter_terrain<R: Rng>(&mut self, center: Coords, rng: &mut R) {
+        // on an endless board, only what's there at the start
+        let tiles: Vec<Coords> = self.iter().map(|(c, _)| c).collect();
+        for terrain in [
+            Terrain::Water,
+            Terrain::Rock,
+            Terrain::HighGround,
+            Terrain::Swamp,
+        ] {
+            for _ in 0..TERRAIN_PATCHES {
+                let middle = match tiles.choose(rng) {
+                    Some(&c) => c,
+                    None => return,
+                };
+                let size = rng.gen_range(0..=2);
+                let mut patch = vec![middle];
+                for r in 1..=size {
+                    patch.append(&mut self.ring(middle, r));
+                }
+                for c in patch {
+                    if self.topology.distance(center, c) > 2 {
+                        self.set_terrain(c, terrain);
+                    }
+                }
+            }
+        }
+    }
+
+    // a hand-made board
+    // everything the map names is decided before generation runs
+    pub fn load_map(&mut self, map: &MapFile) {
+        for &c in map.mines.iter() {
+            if let Some(tile) = self.get_mut(c) {
+                tile.mine = true;
+                tile.decided = true;
+            }
+        }
+        for &c in map.empty.iter() {
+            if let Some(tile) = self.get_mut(c) {
+                tile.decided = true;
+            }
+        }
+        for &(c, terrain) in map.terrain.iter() {
+            self.set_terrain(c, terrain);
+        }
+        for &c in map.floor.iter() {
+            self.reveal(c);
+        }
+        // the revealed numbers are already on show
+        // so generation can't add mines around them
+        for &c in map.floor.iter() {
+            for n in self.number_area(c) {
+                if let Some(tile) = self.get_mut(n) {
+                    tile.decided = true;
+                }
+            }
+        }
+        for &c in map.floor.iter() {
+            if c == map.castle {
+                continue;
+            }
+            let walls = self
+                .number_area(c)
+                .into_iter()
+                .any(|n| matches!(self.state(n), Some(s) if s.is_wall()));
+            if walls {
+                self.set_number(c);
+            }
+        }
+    }
+
+    // takes the mine back out of a wall and fixes the numbers that counted it
+    fn remove_mine(&mut self, coords: Coords) {
+        let weight = match self.get_mut(coords) {
+            Some(tile) => {
+                let weight = tile.mines();
+                tile.mine = false;
+                tile.double = false;
+                weight
+            }
+            None => return,
+        };
+        for n in self.number_area(coords) {
+            if let Some(tile) = self.get_mut(n) {
+                if tile.state == TileState::Floor && tile.clue {
+                    tile.number -= weight;
+                }
+            }
+        }
+    }
+
+    // the numbers just put down count these
+    // chording and flooding can't roll new mines under them later
+    fn decide(&mut self, walls: impl Iterator<Item = Coords>) {
+        for c in walls {
+            if let Some(tile) = self.get_mut(c) {
+                tile.decided = true;
+            }
+        }
+    }
+}
+
+// off the board isn't part of it. Unreserved chunks of an endless board aren't either
+impl GridGraph for Board {
+    fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    fn contains(&self, coords: Coords) -> bool {
+        self.get(coords).is_some()
+    }
+
+    fn cost(&self, coords: Coords) -> Option<u32> {
+        self.path_cost(coords)
+    }
+
+    fn index(&self, coords: Coords) -> Option<usize> {
+        if !self.in_bounds(coords) {
+            return None;
+        }
+        self.tiles.index(coords)
+    }
+
+    fn coords(&self, index: usize) -> Option<Coords> {
+        self.tiles.coords_at(index).filter(|&c| self.in_bounds(c))
+    }
+
+    fn index_len(&self) -> usize {
+        self.tiles.index_len()
+    }
+
+    fn cost_changes(&self) -> &[Coords] {
+        &self.cost_changes
+    }
+}
+
+// mixes the board seed with a tile's coords
+// splitmix64, so tiles next to each other don't get similar rolls
+fn tile_seed(seed: u64, coords: Coords) -> u64 {
+    let xy = ((coords.x as u32 as u64) << 32) | coords.y as u32 as u64;
+    splitmix(seed ^ splitmix(xy))
+}
+
+fn splitmix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// pick count random coords to have bombs
+// sorted first because HashSet order changes from run to run
+// and the same seed has to pick the same tiles
+fn pick_mines<R: Rng>(candidates: HashSet<Coords>, count: usize, rng: &mut R) -> HashSet<Coords> {
+    let mut candidates: Vec<Coords> = candidates.into_iter().collect();
+    candidates.sort();
+    candidates.choose_multiple(rng, count).copied().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+
+    fn territory(expansion: ExpansionRule, radius: i32) -> TerritoryInfo {
+        TerritoryInfo {
+            radius,
+            x: 10,
+            y: 10,
+            bombs_percent: 0.5,
+            expansion,
+            shape: RingShape::Square,
+            no_guess: false,
+        }
+    }
+
+    // a 5x5 board with one mine at (1, 1) and a 1 at (2, 2)
+    fn one_mine() -> Board {
+        let mut board = Board::new(5, 5, Topology::Square, NumberRule::Adjacent);
+        board.get_mut(Coords::new(1, 1)).unwrap().mine = true;
+        board.reveal(Coords::new(2, 2));
+        let tile = board.get_mut(Coords::new(2, 2)).unwrap();
+        tile.number = 1;
+        tile.clue = true;
+        board
+    }
+
+    // every number shows the mines it counts
+    fn assert_numbers(board: &Board) {
+        for (c, tile) in board.iter() {
+            if tile.state != TileState::Floor || !tile.clue {
+                continue;
+            }
+            let mines: usize = board
+                .number_area(c)
+                .into_iter()
+                .filter_map(|n| board.get(n).map(|t| t.mines()))
+                .sum();
+            assert_eq!(tile.number, mines, "number at {:?}", c);
+        }
+    }
+
+    // every mine the new numbers count can be proved from them
+    #[test]
+    fn no_guess_rings_are_solvable() {
+        for expansion in [ExpansionRule::Battlements, ExpansionRule::Square] {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut board = Board::new(21, 21, Topology::Square, NumberRule::Adjacent);
+            for radius in [1, 3, 5] {
+                let mut info = territory(expansion, radius);
+                info.no_guess = true;
+                board.expand(&info, &mut rng);
+                assert_numbers(&board);
+                assert!(solver::unproven_mines(&board).is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn expand_same_seed_same_board() {
+        let build = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut board = Board::new(21, 21, Topology::Square, NumberRule::Adjacent);
+            board.expand(&territory(ExpansionRule::Battlements, 1), &mut rng);
+            board.expand(&territory(ExpansionRule::Battlements, 3), &mut rng);
+            board
+                .iter()
+                .map(|(c, t)| (c, t.mine, t.number))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(build(5), build(5));
+    }
+
+    // rings on endless boards go into negative coords
+    // with every wall a mine, all of them have to get one
+    #[test]
+    fn expand_square_negative_side() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut board = Board::endless(Coords::new(0, 0), Topology::Square, NumberRule::Adjacent);
+        let mut info = territory(ExpansionRule::Square, 1);
+        info.x = 0;
+        info.y = 0;
+        info.bombs_percent = 1.0;
+        board.expand(&info, &mut rng);
+        assert_numbers(&board);
+        for c in board.ring(Coords::new(0, 0), 2) {
+            assert!(board.get(c).unwrap().mine, "no mine at {:?}", c);
+        }
+    }
+
+    // 2 away and not in a line, on both kinds of row
+    // and a counts b whenever b counts a
+    #[test]
+    fn hex_knight() {
+        for c in [Coords::new(4, 4), Coords::new(4, 5)] {
+            let knights = c.get_hex_knight_coords();
+            assert_eq!(knights.len(), 6);
+            for k in knights {
+                assert_eq!(c.hex_distance(k), 2);
+                assert!(k.get_hex_knight_coords().contains(&c));
+            }
+        }
+    }
+
+    const RULES: [NumberRule; 4] = [
+        NumberRule::Adjacent,
+        NumberRule::KnightsMove,
+        NumberRule::Ring5x5,
+        NumberRule::DoubleMines,
+    ];
+
+    // a counts b whenever b counts a, or towers would fill the wrong numbers
+    #[test]
+    fn number_areas_go_both_ways() {
+        for topology in [Topology::Square, Topology::Hex] {
+            for rule in RULES {
+                let board = Board::new(11, 11, topology, rule);
+                let c = Coords::new(5, 5);
+                for n in board.number_area(c) {
+                    assert!(
+                        board.number_area(n).contains(&c),
+                        "{:?} {:?}",
+                        topology,
+                        rule
+                    );
+                }
+            }
+        }
+    }
+
+    const SHAPES: [RingShape; 4] = [
+        RingShape::Square,
+        RingShape::Diamond,
+        RingShape::Circle,
+        RingShape::Noise,
+    ];
+
+    // every topology, rule, shape and expansion
+    // keeps the castle open and the numbers right
+    #[test]
+    fn numbers_count_mines() {
+        for topology in [Topology::Square, Topology::Hex] {
+            for rule in RULES {
+                for shape in SHAPES {
+                    for expansion in [ExpansionRule::Battlements, ExpansionRule::Square] {
+                        let mut rng = StdRng::seed_from_u64(0);
+                        let mut board = Board::new(21, 21, topology, rule);
+                        for radius in [1, 3, 5] {
+                            let mut info = territory(expansion, radius);
+                            info.shape = shape;
+                            board.expand(&info, &mut rng);
+                            assert_numbers(&board);
+                        }
+                        assert_eq!(
+                            board.state(Coords::new(10, 10)),
+                            Some(TileState::Floor),
+                            "{:?} {:?} {:?} {:?}",
+                            topology,
+                            rule,
+                            shape,
+                            expansion
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // a diamond ring is every tile r steps away without diagonals
+    #[test]
+    fn diamond_ring() {
+        let board = Board::new(21, 21, Topology::Square, NumberRule::Adjacent);
+        let center = Coords::new(10, 10);
+        for radius in 1..=5 {
+            let ring: HashSet<Coords> = board
+                .shape_ring(center, radius, RingShape::Diamond)
+                .into_iter()
+                .collect();
+            let expected: HashSet<Coords> = board
+                .iter()
+                .map(|(c, _)| c)
+                .filter(|c| (c.x - center.x).abs() + (c.y - center.y).abs() == radius)
+                .collect();
+            assert_eq!(ring, expected, "radius {}", radius);
+            assert_eq!(ring.len(), 4 * radius as usize);
+        }
+    }
+
+    // a circle ring is every tile within half a tile of r in a straight line
+    // so the corners of the square are left for later rings
+    #[test]
+    fn circle_ring() {
+        let board = Board::new(21, 21, Topology::Square, NumberRule::Adjacent);
+        let center = Coords::new(10, 10);
+        for radius in 1..=5 {
+            let ring = board.shape_ring(center, radius, RingShape::Circle);
+            for c in ring.iter() {
+                let dx = (c.x - center.x) as f32;
+                let dy = (c.y - center.y) as f32;
+                let distance = (dx * dx + dy * dy).sqrt();
+                assert!(
+                    (distance - radius as f32).abs() <= 0.5,
+                    "{:?} is {} away, not {}",
+                    c,
+                    distance,
+                    radius
+                );
+            }
+            assert!(ring.contains(&Coords::new(10 + radius, 10)));
+            assert!(ring.contains(&Coords::new(10, 10 - radius)));
+            if radius > 1 {
+                assert!(!ring.contains(&Coords::new(10 + radius, 10 + radius)));
+            }
+        }
+    }
+
+    #[test]
+    fn place_tower() {
+        let mut board = one_mine();
+        assert!(board.try_place_tower(Coords::new(1, 1)).is_ok());
+        assert_eq!(board.state(Coords::new(1, 1)), Some(TileState::Tower));
+        assert!(matches!(
+            board.try_place_tower(Coords::new(1, 1)),
+            Err(PlaceError::TowerAlready)
+        ));
+        assert!(matches!(
+            board.try_place_tower(Coords::new(2, 2)),
+            Err(PlaceError::Floor)
+        ));
+        assert!(matches!(
+            board.try_place_tower(Coords::new(7, 7)),
+            Err(PlaceError::OffBoard)
+        ));
+        board.toggle_flag(Coords::new(3, 3));
+        assert!(matches!(
+            board.try_place_tower(Coords::new(3, 3)),
+            Err(PlaceError::Flagged)
+        ));
+    }
+
+    #[test]
+    fn count_tower_on_mine_fills() {
+        let mut board = one_mine();
+        board.try_place_tower(Coords::new(1, 1)).unwrap();
+        assert_eq!(
+            board.count_tower(Coords::new(1, 1)),
+            vec![Coords::new(2, 2)]
+        );
+        assert_eq!(board.remaining(Coords::new(2, 2)), 0);
+        assert_eq!(board.contradiction(Coords::new(2, 2)), None);
+    }
+
+    #[test]
+    fn count_tower_on_empty_is_a_guess() {
+        let mut board = one_mine();
+        board.try_place_tower(Coords::new(3, 3)).unwrap();
+        assert!(board.count_tower(Coords::new(3, 3)).is_empty());
+        let tile = board.get(Coords::new(2, 2)).unwrap();
+        assert_eq!((tile.towers, tile.found), (1, 0));
+        // shows 0, but the mine is still out there
+        assert_eq!(board.remaining(Coords::new(2, 2)), 0);
+    }
+
+    // one tower on a double mine fills a 2 by itself
+    #[test]
+    fn count_tower_on_double_mine() {
+        let mut board = Board::new(5, 5, Topology::Square, NumberRule::DoubleMines);
+        let tile = board.get_mut(Coords::new(1, 1)).unwrap();
+        tile.mine = true;
+        tile.double = true;
+        board.reveal(Coords::new(2, 2));
+        let tile = board.get_mut(Coords::new(2, 2)).unwrap();
+        tile.number = 2;
+        tile.clue = true;
+
+        board.try_place_tower(Coords::new(1, 1)).unwrap();
+        assert_eq!(
+            board.count_tower(Coords::new(1, 1)),
+            vec![Coords::new(2, 2)]
+        );
+        let tile = board.get(Coords::new(2, 2)).unwrap();
+        assert_eq!((tile.towers, tile.found), (2, 2));
+        assert_eq!(board.remaining(Coords::new(2, 2)), 0);
+        assert_eq!(board.contradiction(Coords::new(2, 2)), None);
+    }
+
+    // the 8 knight jumps and nothing next to it
+    #[test]
+    fn knights_move_counts_knight_tiles() {
+        let mut board = Board::new(9, 9, Topology::Square, NumberRule::KnightsMove);
+        let c = Coords::new(4, 4);
+        let jumps = [
+            (1, 2),
+            (2, 1),
+            (2, -1),
+            (1, -2),
+            (-1, -2),
+            (-2, -1),
+            (-2, 1),
+            (-1, 2),
+        ];
+        let expected: HashSet<Coords> = jumps
+            .iter()
+            .map(|&(dx, dy)| Coords::new(c.x + dx, c.y + dy))
+            .collect();
+        let area: HashSet<Coords> = board.number_area(c).into_iter().collect();
+        assert_eq!(area, expected);
+
+        // a tower next to the number doesn't count for it, one a jump away does
+        board.get_mut(Coords::new(5, 5)).unwrap().mine = true;
+        board.get_mut(Coords::new(5, 6)).unwrap().mine = true;
+        board.reveal(c);
+        let tile = board.get_mut(c).unwrap();
+        tile.number = 1;
+        tile.clue = true;
+        board.try_place_tower(Coords::new(5, 5)).unwrap();
+        assert!(board.count_tower(Coords::new(5, 5)).is_empty());
+        board.try_place_tower(Coords::new(5, 6)).unwrap();
+        assert_eq!(board.count_tower(Coords::new(5, 6)), vec![c]);
+    }
+
+    // a guessed tower doesn't fill the number, so it can't be chorded
+    #[test]
+    fn guess_next_to_mine() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut board = one_mine();
+        board.try_place_tower(Coords::new(3, 3)).unwrap();
+        board.count_tower(Coords::new(3, 3));
+        board.try_place_tower(Coords::new(1, 1)).unwrap();
+        assert_eq!(
+            board.count_tower(Coords::new(1, 1)),
+            vec![Coords::new(2, 2)]
+        );
+
+        let tile = board.get(Coords::new(2, 2)).unwrap();
+        assert_eq!((tile.number, tile.towers, tile.found), (1, 2, 1));
+        assert!(!board.is_satisfied(Coords::new(2, 2)));
+        assert!(board.chord(Coords::new(2, 2), 0.0).is_empty());
+        assert!(board.is_misplaced(Coords::new(3, 3)));
+        assert!(!board.is_misplaced(Coords::new(1, 1)));
+    }
+
+    #[test]
+    fn contradictions() {
+        let mut board = one_mine();
+        let number = Coords::new(2, 2);
+        for c in [Coords::new(1, 1), Coords::new(3, 3)] {
+            board.try_place_tower(c).unwrap();
+            board.count_tower(c);
+        }
+        assert_eq!(
+            board.contradiction(number),
+            Some(Contradiction::TooManyTowers)
+        );
+
+        // digging out every wall leaves nowhere for the mine
+        let mut board = one_mine();
+        for c in board.number_area(number) {
+            board.reveal(c);
+        }
+        assert_eq!(
+            board.contradiction(number),
+            Some(Contradiction::NotEnoughWalls)
+        );
+    }
+
+    #[test]
+    fn terrain() {
+        let mut board = one_mine();
+        let c = Coords::new(1, 1);
+        board.set_terrain(c, Terrain::Water);
+        assert_eq!(board.state(c), Some(TileState::Blocked));
+        assert!(!board.get(c).unwrap().mine);
+        assert!(matches!(board.try_place_tower(c), Err(PlaceError::Blocked)));
+
+        board.set_terrain(c, Terrain::Swamp);
+        assert_eq!(board.state(c), Some(TileState::Wall));
+        board.reveal(c);
+        // the ground stays when the wall is cleared
+        assert_eq!(board.terrain(c), Terrain::Swamp);
+        assert_eq!(board.terrain(c).movement_cost(), Some(3));
+    }
+
+    // two numbers share walls, so the order they're chorded in
+    // decides which one rolls them. The rolls can't depend on it
+    #[test]
+    fn chord_order_doesnt_matter() {
+        let build = |order: [Coords; 2]| {
+            let mut rng = StdRng::seed_from_u64(0);
+            let mut board = Board::new(21, 21, Topology::Square, NumberRule::Adjacent);
+            board.set_seed(7);
+            let mut info = territory(ExpansionRule::Square, 1);
+            info.bombs_percent = 0.0;
+            board.expand(&info, &mut rng);
+            for c in order {
+                board.chord(c, 0.5);
+            }
+            board
+                .iter()
+                .map(|(c, t)| (c, t.state, t.mine, t.number))
+                .collect::<Vec<_>>()
+        };
+        let a = Coords::new(11, 10);
+        let b = Coords::new(11, 11);
+        assert_eq!(build([a, b]), build([b, a]));
+    }
+
+    #[test]
+    fn reveal() {
+        let mut board = one_mine();
+        assert!(board.reveal(Coords::new(1, 1)));
+        let tile = board.get(Coords::new(1, 1)).unwrap();
+        assert_eq!(tile.state, TileState::Floor);
+        // whatever was under it is gone
+        assert!(!tile.mine);
+        // already floor
+        assert!(!board.reveal(Coords::new(1, 1)));
+        board.try_place_tower(Coords::new(3, 3)).unwrap();
+        assert!(!board.reveal(Coords::new(3, 3)));
+        assert!(!board.reveal(Coords::new(9, 9)));
+    }
+
+    #[test]
+    fn toggle_flag() {
+        let mut board = one_mine();
+        let c = Coords::new(1, 1);
+        assert!(board.toggle_flag(c));
+        assert_eq!(board.state(c), Some(TileState::Flagged));
+        // still a wall as far as the numbers go
+        assert!(board.state(c).unwrap().is_wall());
+        assert!(board.toggle_flag(c));
+        assert_eq!(board.state(c), Some(TileState::Wall));
+        assert!(!board.toggle_flag(Coords::new(2, 2)));
+    }
+
+    // a flag is only a note, the territory still clears it
+    #[test]
+    fn flags_stay_walls() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut board = Board::new(21, 21, Topology::Square, NumberRule::Adjacent);
+        board.expand(&territory(ExpansionRule::Square, 1), &mut rng);
+        let flag = Coords::new(12, 12);
+        let number = board.get(Coords::new(11, 11)).unwrap().number;
+        board.toggle_flag(flag);
+        assert_eq!(board.remaining(Coords::new(11, 11)), number);
+        board.expand(&territory(ExpansionRule::Square, 3), &mut rng);
+        assert_eq!(board.state(flag), Some(TileState::Floor));
+    }
+
+    // the board knows a guess from a mine
+    #[test]
+    fn misplaced_tower() {
+        let mut board = one_mine();
+        board.try_place_tower(Coords::new(3, 3)).unwrap();
+        board.count_tower(Coords::new(3, 3));
+        assert!(board.is_misplaced(Coords::new(3, 3)));
+        board.try_place_tower(Coords::new(1, 1)).unwrap();
+        assert!(!board.is_misplaced(Coords::new(1, 1)));
+        assert!(!board.is_misplaced(Coords::new(2, 2)));
+    }
+
+    #[test]
+    fn hex_rings() {
+        let board = Board::new(21, 21, Topology::Hex, NumberRule::Adjacent);
+        for c in [Coords::new(10, 10), Coords::new(10, 11)] {
+            let neighbours: HashSet<Coords> = board.neighbours(c).collect();
+            assert_eq!(neighbours, board.ring(c, 1).into_iter().collect());
+            for n in neighbours {
+                assert!(board.neighbours(n).any(|b| b == c));
+            }
+            for radius in 1..=4 {
+                assert_eq!(board.ring(c, radius).len(), 6 * radius as usize);
+            }
+        }
+    }
+
+    // zeros keep going until they hit numbers
+    #[test]
+    fn flood_through_zeros() {
+        let mut board = Board::new(9, 9, Topology::Square, NumberRule::Adjacent);
+        let mut info = territory(ExpansionRule::FloodFill, 1);
+        info.x = 4;
+        info.y = 4;
+        info.bombs_percent = 0.0;
+        board.expand(&info, &mut StdRng::seed_from_u64(0));
+        assert!(board.iter().all(|(_, t)| t.state == TileState::Floor));
+
+        for seed in 0..10 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut board = Board::new(21, 21, Topology::Square, NumberRule::Adjacent);
+            info.x = 10;
+            info.y = 10;
+            info.bombs_percent = 0.2;
+            board.expand(&info, &mut rng);
+            assert_numbers(&board);
+            for (c, _) in board.iter() {
+                if board.is_satisfied(c) {
+                    assert!(board
+                        .number_area(c)
+                        .into_iter()
+                        .all(|n| board.state(n) != Some(TileState::Wall)));
+                }
+            }
+        }
+    }
+
+    // walls past the territory are rolled when a chord reaches them
+    // and the walls the ring's numbers counted stay as they were
+    #[test]
+    fn chord_rolls_new_walls() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut board = Board::new(21, 21, Topology::Square, NumberRule::Adjacent);
+        let mut info = territory(ExpansionRule::Square, 1);
+        info.bombs_percent = 0.0;
+        board.expand(&info, &mut rng);
+
+        let cleared = board.chord(Coords::new(11, 10), 1.0);
+        assert_eq!(cleared.len(), 3);
+        assert_numbers(&board);
+        // only the column past the cleared tiles is new
+        assert_eq!(board.get(Coords::new(12, 10)).unwrap().number, 3);
+        assert_eq!(board.get(Coords::new(12, 9)).unwrap().number, 3);
+        assert!(!board.is_satisfied(Coords::new(12, 10)));
+
+        // growing over the chorded tiles leaves no stale numbers
+        board.expand(&territory(ExpansionRule::Square, 3), &mut rng);
+        assert_numbers(&board);
+    }
+}
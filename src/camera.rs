@@ -0,0 +1,202 @@
+use bevy::{
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    prelude::*,
+};
+
+use crate::{board::Coords, castle::TerritoryInfo, grid::Grid, GameState, MouseWorldPos};
+
+// scroll to zoom
+// drag with the middle mouse, push the mouse to the edge, or use WASD/arrows to pan
+// C jumps back to the castle
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_startup_system(spawn_camera)
+            .add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(reset_camera))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(zoom)
+                    .with_system(pan)
+                    .with_system(drag)
+                    .with_system(focus_castle),
+            )
+            // big maps need moving around too
+            .add_system_set(
+                SystemSet::on_update(GameState::Editor)
+                    .with_system(zoom)
+                    .with_system(pan)
+                    .with_system(drag),
+            );
+    }
+}
+
+// how much one scroll click zooms
+const ZOOM_STEP: f32 = 0.1;
+// scale of the projection. Smaller is closer
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 3.0;
+// trackpads scroll in pixels. This many is one click
+const PIXELS_PER_LINE: f32 = 100.0;
+// pixels per second at zoom 1
+const PAN_SPEED: f32 = 500.0;
+// how close to the edge of the play area the mouse has to be to pan
+const EDGE_PAN_MARGIN: f32 = 20.0;
+
+// Components
+// the camera the game is seen through
+// anything picking tiles with the mouse should use this one
+#[derive(Component)]
+pub struct GameCamera;
+
+fn spawn_camera(mut commands: Commands) {
+    commands
+        .spawn_bundle(Camera2dBundle::default())
+        .insert(GameCamera);
+}
+
+// the menu and the start of a game look at the whole board
+fn reset_camera(
+    mut q_camera: Query<(&mut Transform, &mut OrthographicProjection), With<GameCamera>>,
+) {
+    for (mut trans, mut projection) in q_camera.iter_mut() {
+        trans.translation.x = 0.0;
+        trans.translation.y = 0.0;
+        projection.scale = 1.0;
+    }
+}
+
+// zooms around the mouse so the tile under it stays under it
+fn zoom(
+    mut ev_wheel: EventReader<MouseWheel>,
+    mouse: Res<MouseWorldPos>,
+    mut q_camera: Query<(&mut Transform, &mut OrthographicProjection), With<GameCamera>>,
+) {
+    let mut clicks = 0.0;
+    for ev in ev_wheel.iter() {
+        clicks += match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / PIXELS_PER_LINE,
+        };
+    }
+    if clicks == 0.0 {
+        return;
+    }
+    for (mut trans, mut projection) in q_camera.iter_mut() {
+        let old = projection.scale;
+        // scroll up to get closer
+        projection.scale = (old * (1.0 - clicks * ZOOM_STEP)).clamp(MIN_ZOOM, MAX_ZOOM);
+        let pos = trans.translation.truncate();
+        let new_pos = mouse.0 + (pos - mouse.0) * (projection.scale / old);
+        trans.translation.x = new_pos.x;
+        trans.translation.y = new_pos.y;
+    }
+}
+
+fn pan(
+    keyboard: Res<Input<KeyCode>>,
+    windows: Res<Windows>,
+    time: Res<Time>,
+    q_ui: Query<(&Node, &GlobalTransform, &Visibility)>,
+    mut q_camera: Query<(&mut Transform, &OrthographicProjection), With<GameCamera>>,
+) {
+    let mut dir = Vec2::ZERO;
+    if keyboard.any_pressed([KeyCode::W, KeyCode::Up]) {
+        dir.y += 1.0;
+    }
+    if keyboard.any_pressed([KeyCode::S, KeyCode::Down]) {
+        dir.y -= 1.0;
+    }
+    if keyboard.any_pressed([KeyCode::A, KeyCode::Left]) {
+        dir.x -= 1.0;
+    }
+    if keyboard.any_pressed([KeyCode::D, KeyCode::Right]) {
+        dir.x += 1.0;
+    }
+
+    // cursor_position is None when the mouse is outside the window
+    if let Some(win) = windows.get_primary() {
+        if let Some(cursor) = win.cursor_position() {
+            // ui nodes and the cursor are both in window pixels, from the bottom left
+            let ui: Vec<(Vec2, Vec2)> = q_ui
+                .iter()
+                .filter(|(_, _, visibility)| visibility.is_visible)
+                .map(|(node, trans, _)| {
+                    let pos = trans.translation().truncate();
+                    (pos - node.size / 2.0, pos + node.size / 2.0)
+                })
+                .collect();
+            let over_ui = ui.iter().any(|&(min, max)| {
+                cursor.x >= min.x && cursor.x < max.x && cursor.y >= min.y && cursor.y < max.y
+            });
+
+            // don't move while picking a tower
+            if !over_ui {
+                // the menus down the sides cover the edges of the window
+                // so measure from the edges of the board's part instead
+                let panels = ui.iter().filter(|(min, max)| max.y - min.y >= win.height());
+                let left = panels
+                    .clone()
+                    .filter(|(min, _)| min.x <= 0.0)
+                    .fold(0.0, |left: f32, (_, max)| left.max(max.x));
+                let right = panels
+                    .filter(|(_, max)| max.x >= win.width())
+                    .fold(win.width(), |right: f32, (min, _)| right.min(min.x));
+
+                if cursor.x < left + EDGE_PAN_MARGIN {
+                    dir.x -= 1.0;
+                } else if cursor.x > right - EDGE_PAN_MARGIN {
+                    dir.x += 1.0;
+                }
+                // screen y goes up from the bottom
+                if cursor.y < EDGE_PAN_MARGIN {
+                    dir.y -= 1.0;
+                } else if cursor.y > win.height() - EDGE_PAN_MARGIN {
+                    dir.y += 1.0;
+                }
+            }
+        }
+    }
+
+    if dir == Vec2::ZERO {
+        return;
+    }
+    for (mut trans, projection) in q_camera.iter_mut() {
+        // move the same speed across the screen at any zoom
+        let step = dir.normalize() * PAN_SPEED * projection.scale * time.delta_seconds();
+        trans.translation += step.extend(0.0);
+    }
+}
+
+fn drag(
+    mouse_buttons: Res<Input<MouseButton>>,
+    mut ev_motion: EventReader<MouseMotion>,
+    mut q_camera: Query<(&mut Transform, &OrthographicProjection), With<GameCamera>>,
+) {
+    let delta = ev_motion.iter().fold(Vec2::ZERO, |sum, ev| sum + ev.delta);
+    if !mouse_buttons.pressed(MouseButton::Middle) || delta == Vec2::ZERO {
+        return;
+    }
+    for (mut trans, projection) in q_camera.iter_mut() {
+        // the board follows the mouse. Motion y goes down the screen
+        trans.translation.x -= delta.x * projection.scale;
+        trans.translation.y += delta.y * projection.scale;
+    }
+}
+
+fn focus_castle(
+    keyboard: Res<Input<KeyCode>>,
+    grid: Res<Grid>,
+    territory_info: Res<TerritoryInfo>,
+    mut q_camera: Query<&mut Transform, With<GameCamera>>,
+) {
+    if !keyboard.just_pressed(KeyCode::C) {
+        return;
+    }
+    let castle = Coords::new(territory_info.x as i32, territory_info.y as i32);
+    let pos = grid.tile_to_world(castle);
+    for mut trans in q_camera.iter_mut() {
+        trans.translation.x = pos.x;
+        trans.translation.y = pos.y;
+    }
+}
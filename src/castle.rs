@@ -2,9 +2,10 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 use crate::{
+    board::{Board, Coords, ExpansionRule, RingShape},
     director::EndScreenEvent,
     enemy::Enemy,
-    grid::{Grid, Tile},
+    grid::{Grid, GridConfig, MisplacedTowerEvent, Tile},
     loading::SpriteAssets,
     GameState,
 };
@@ -13,9 +14,10 @@ pub struct CastlePlugin;
 
 impl Plugin for CastlePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(TerritoryInfo::new())
+        app.insert_resource(TerritoryInfo::new(&GridConfig::default()))
             .add_event::<NumberFilledEvent>()
-            .add_event::<ExpandAreaEvent>();
+            .add_event::<ExpandAreaEvent>()
+            .add_event::<ChordEvent>();
 
         // app.add_system_set(SystemSet::on_exit(GameState::MainMenu).with_system(startup))
         app.add_system_set(
@@ -23,10 +25,18 @@ impl Plugin for CastlePlugin {
                 .with_system(startup)
                 .with_system(spawn_castle.after(startup)),
         )
+        .add_system_set(
+            SystemSet::on_update(GameState::MainMenu)
+                .with_system(toggle_no_guess)
+                .with_system(cycle_shape)
+                .with_system(cycle_expansion),
+        )
         // update
         .add_system_set(
             SystemSet::on_update(GameState::Playing)
                 .with_system(number_filled)
+                .with_system(flood_reach)
+                .with_system(misplaced_tower)
                 .with_system(enemy_collision),
         );
 
@@ -46,24 +56,39 @@ pub struct TerritoryInfo {
     pub x: usize,
     pub y: usize,
     pub bombs_percent: f32,
-    pub battlements_style: bool,
+    pub expansion: ExpansionRule,
+    // the outline each ring makes
+    pub shape: RingShape,
+    // re-roll each ring until it can be solved without guessing
+    pub no_guess: bool,
 }
 
 impl TerritoryInfo {
-    fn new() -> Self {
+    fn new(config: &GridConfig) -> Self {
+        let (x, y) = config.center();
         TerritoryInfo {
             radius: 1,
-            x: 10,
-            y: 10,
+            x,
+            y,
             bombs_percent: 0.5,
-            battlements_style: true,
+            expansion: ExpansionRule::Battlements,
+            shape: RingShape::Square,
+            no_guess: false,
         }
     }
 }
 
 // events
-pub struct NumberFilledEvent;
+pub struct NumberFilledEvent {
+    pub x: i32,
+    pub y: i32,
+}
 pub struct ExpandAreaEvent;
+// clicked a number. If it's satisfied, clear the walls around it
+pub struct ChordEvent {
+    pub x: i32,
+    pub y: i32,
+}
 
 #[derive(Component)]
 pub struct Castle {
@@ -85,21 +110,57 @@ impl Castle {
     }
 }
 
-fn startup(mut ev_expand: EventWriter<ExpandAreaEvent>) {
+fn startup(
+    mut ev_expand: EventWriter<ExpandAreaEvent>,
+    mut territory_info: ResMut<TerritoryInfo>,
+    config: Res<GridConfig>,
+) {
+    // centre the territory on whatever board this run uses
+    // keep the modes picked on the menu
+    let no_guess = territory_info.no_guess;
+    let expansion = territory_info.expansion;
+    let shape = territory_info.shape;
+    *territory_info = TerritoryInfo::new(&config);
+    territory_info.no_guess = no_guess;
+    territory_info.expansion = expansion;
+    territory_info.shape = shape;
     // if keyboard.just_pressed(KeyCode::F) {
     ev_expand.send(ExpandAreaEvent);
     // }
 }
 
+fn toggle_no_guess(keyboard: Res<Input<KeyCode>>, mut territory_info: ResMut<TerritoryInfo>) {
+    if keyboard.just_pressed(KeyCode::G) {
+        territory_info.no_guess = !territory_info.no_guess;
+        println!("No guess mode: {}", territory_info.no_guess);
+    }
+}
+
+fn cycle_expansion(keyboard: Res<Input<KeyCode>>, mut territory_info: ResMut<TerritoryInfo>) {
+    if keyboard.just_pressed(KeyCode::E) {
+        territory_info.expansion = territory_info.expansion.next();
+        println!("Expansion: {:?}", territory_info.expansion);
+    }
+}
+
+fn cycle_shape(keyboard: Res<Input<KeyCode>>, mut territory_info: ResMut<TerritoryInfo>) {
+    if keyboard.just_pressed(KeyCode::R) {
+        territory_info.shape = territory_info.shape.next();
+        println!("Ring shape: {:?}", territory_info.shape);
+    }
+}
+
 fn spawn_castle(
     mut commands: Commands,
     q_tiles: Query<Entity, With<Tile>>,
     grid: Res<Grid>,
+    territory_info: Res<TerritoryInfo>,
     textures: Res<SpriteAssets>,
     // keyboard: Res<Input<KeyCode>>,
 ) {
     // if keyboard.just_pressed(KeyCode::C) {
-    if let Some(info) = grid.get_xy(10, 10) {
+    let castle = Coords::new(territory_info.x as i32, territory_info.y as i32);
+    if let Some(info) = grid.get(castle) {
         if let Ok(ent) = q_tiles.get(info.entity) {
             let child = commands
                 .spawn_bundle(SpriteBundle {
@@ -126,11 +187,17 @@ fn spawn_castle(
 }
 
 fn number_filled(
-    ev_number_filled: EventReader<NumberFilledEvent>,
+    mut ev_number_filled: EventReader<NumberFilledEvent>,
     mut ev_expand: EventWriter<ExpandAreaEvent>,
+    mut ev_chord: EventWriter<ChordEvent>,
     mut info: ResMut<TerritoryInfo>,
 ) {
-    if !ev_number_filled.is_empty() {
+    if info.expansion == ExpansionRule::FloodFill {
+        // only open up around the numbers that were filled
+        for ev in ev_number_filled.iter() {
+            ev_chord.send(ChordEvent { x: ev.x, y: ev.y });
+        }
+    } else if !ev_number_filled.is_empty() {
         ev_number_filled.clear();
         println!("Number filled. Expand reach");
         info.radius += 2;
@@ -147,6 +214,43 @@ fn number_filled(
     }
 }
 
+// flood fill grows wherever the player works
+// every time it reaches another 2 out, count it as a new ring
+// so the director and the mines keep up
+fn flood_reach(
+    board: Res<Board>,
+    mut info: ResMut<TerritoryInfo>,
+    mut ev_expand: EventWriter<ExpandAreaEvent>,
+) {
+    if info.expansion != ExpansionRule::FloodFill || !board.is_changed() {
+        return;
+    }
+    let center = Coords::new(info.x as i32, info.y as i32);
+    let reach = board.reach(center);
+    while reach >= info.radius + 2 {
+        println!("Flood reached {}", reach);
+        info.radius += 2;
+        info.bombs_percent += 0.1;
+        ev_expand.send(ExpandAreaEvent);
+    }
+}
+
+// building on a tile without a mine hurts the castle
+// so guessing costs more than the tower
+const MISPLACED_TOWER_DAMAGE: u32 = 2;
+
+fn misplaced_tower(
+    mut ev_misplaced: EventReader<MisplacedTowerEvent>,
+    mut q_castle: Query<&mut Castle>,
+) {
+    for ev in ev_misplaced.iter() {
+        for mut castle in q_castle.iter_mut() {
+            println!("Tower at {}, {} wasn't on a mine", ev.x, ev.y);
+            castle.take_damage(MISPLACED_TOWER_DAMAGE);
+        }
+    }
+}
+
 fn enemy_collision(
     mut commands: Commands,
     rapier_context: Res<RapierContext>,
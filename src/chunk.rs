@@ -0,0 +1,118 @@
+use std::collections::BTreeMap;
+
+use crate::board::Coords;
+
+// tiles are stored in square chunks so the board can keep growing
+// in any direction, negative coords included
+pub const CHUNK_SIZE: i32 = 16;
+
+// the chunk a tile is in
+pub fn chunk_of(coords: Coords) -> Coords {
+    Coords::new(
+        coords.x.div_euclid(CHUNK_SIZE),
+        coords.y.div_euclid(CHUNK_SIZE),
+    )
+}
+
+// every tile in a chunk, in storage order
+pub fn chunk_tiles(chunk: Coords) -> impl Iterator<Item = Coords> {
+    (0..CHUNK_SIZE).flat_map(move |x| {
+        (0..CHUNK_SIZE)
+            .map(move |y| Coords::new(chunk.x * CHUNK_SIZE + x, chunk.y * CHUNK_SIZE + y))
+    })
+}
+
+// the chunks that cover the square around center
+pub fn chunks_around(center: Coords, radius: i32) -> Vec<Coords> {
+    let min = chunk_of(Coords::new(center.x - radius, center.y - radius));
+    let max = chunk_of(Coords::new(center.x + radius, center.y + radius));
+    (min.x..=max.x)
+        .flat_map(|x| (min.y..=max.y).map(move |y| Coords::new(x, y)))
+        .collect()
+}
+
+// a BTreeMap so iterating is in the same order every run
+// the same seed has to make the same board
+// chunks are stored in the order they were made so tile indices never move
+#[derive(Clone)]
+pub struct Chunks<T> {
+    slots: BTreeMap<Coords, usize>,
+    chunks: Vec<(Coords, Vec<T>)>,
+    // what a new chunk is filled with
+    fill: T,
+}
+
+impl<T: Clone> Chunks<T> {
+    pub fn new(fill: T) -> Self {
+        Chunks {
+            slots: BTreeMap::new(),
+            chunks: Vec::new(),
+            fill,
+        }
+    }
+
+    fn local_index(coords: Coords) -> usize {
+        let x = coords.x.rem_euclid(CHUNK_SIZE);
+        let y = coords.y.rem_euclid(CHUNK_SIZE);
+        (x * CHUNK_SIZE + y) as usize
+    }
+
+    // false if it was already there
+    pub fn insert_chunk(&mut self, chunk: Coords) -> bool {
+        if self.slots.contains_key(&chunk) {
+            return false;
+        }
+        let size = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+        self.slots.insert(chunk, self.chunks.len());
+        self.chunks.push((chunk, vec![self.fill.clone(); size]));
+        true
+    }
+
+    pub fn has_chunk(&self, chunk: Coords) -> bool {
+        self.slots.contains_key(&chunk)
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = Coords> + '_ {
+        self.slots.keys().copied()
+    }
+
+    // a number for every tile in every chunk so far
+    // making more chunks doesn't change the old numbers
+    pub fn index(&self, coords: Coords) -> Option<usize> {
+        let slot = self.slots.get(&chunk_of(coords))?;
+        Some(slot * (CHUNK_SIZE * CHUNK_SIZE) as usize + Self::local_index(coords))
+    }
+
+    pub fn coords_at(&self, index: usize) -> Option<Coords> {
+        let size = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+        let (chunk, _) = self.chunks.get(index / size)?;
+        // the other way round from local_index
+        let local = (index % size) as i32;
+        Some(Coords::new(
+            chunk.x * CHUNK_SIZE + local / CHUNK_SIZE,
+            chunk.y * CHUNK_SIZE + local % CHUNK_SIZE,
+        ))
+    }
+
+    // one more than the biggest index
+    pub fn index_len(&self) -> usize {
+        self.chunks.len() * (CHUNK_SIZE * CHUNK_SIZE) as usize
+    }
+
+    // None if the chunk hasn't been made yet
+    pub fn get(&self, coords: Coords) -> Option<&T> {
+        let slot = *self.slots.get(&chunk_of(coords))?;
+        Some(&self.chunks[slot].1[Self::local_index(coords)])
+    }
+
+    pub fn get_mut(&mut self, coords: Coords) -> Option<&mut T> {
+        let slot = *self.slots.get(&chunk_of(coords))?;
+        Some(&mut self.chunks[slot].1[Self::local_index(coords)])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Coords, &T)> + '_ {
+        self.slots
+            .iter()
+            .flat_map(move |(&chunk, &slot)| chunk_tiles(chunk).zip(self.chunks[slot].1.iter()))
+    }
+}
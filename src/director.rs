@@ -1,7 +1,15 @@
 use bevy::prelude::*;
 use rand::prelude::*;
 
-use crate::{castle::ExpandAreaEvent, enemy::spawn_enemy, loading::SpriteAssets, GameState};
+use crate::{
+    board::Coords,
+    castle::ExpandAreaEvent,
+    enemy::spawn_enemy,
+    grid::{Grid, GridConfig},
+    loading::SpriteAssets,
+    map::LoadedMap,
+    GameState,
+};
 
 pub struct DirectorPlugin;
 
@@ -11,6 +19,7 @@ impl Plugin for DirectorPlugin {
             .add_event::<EndGameEvent>()
             .add_event::<EndScreenEvent>()
             .insert_resource(SpawnInfo::new())
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(setup_spawn_points))
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                     .with_system(spawn_tick)
@@ -30,6 +39,10 @@ pub struct SpawnInfo {
     pub enemy_health: u32,
     pub next_strat: SpawnStrat,
     pub positions: Vec<Vec2>,
+    // from a map. Empty spawns off the edge
+    pub points: Vec<Vec2>,
+    // where enemies are headed
+    pub castle: Vec2,
 }
 
 impl SpawnInfo {
@@ -41,7 +54,9 @@ impl SpawnInfo {
             difficulty: 0,
             enemy_health: 5,
             next_strat: SpawnStrat::Spread,
-            positions: get_spread_positions(4),
+            positions: get_spread_positions(4, &[]),
+            points: Vec::new(),
+            castle: Vec2::ZERO,
         }
     }
 
@@ -79,6 +94,7 @@ fn spawn_tick(
             spawn_enemy(
                 &mut commands,
                 p.extend(0.4),
+                spawn_info.castle,
                 spawn_info.enemy_health,
                 &textures,
             );
@@ -98,22 +114,22 @@ fn spawn_tick(
             0 => {
                 // println!("Next is Burst");
                 spawn_info.next_strat = SpawnStrat::Burst;
-                spawn_info.positions = get_burst_positions(num);
+                spawn_info.positions = get_burst_positions(num, &spawn_info.points);
             }
             1 => {
                 // println!("Next is Spread");
                 spawn_info.next_strat = SpawnStrat::Spread;
-                spawn_info.positions = get_spread_positions(num);
+                spawn_info.positions = get_spread_positions(num, &spawn_info.points);
             }
             2 => {
                 // println!("Next is Line");
                 spawn_info.next_strat = SpawnStrat::Line;
-                spawn_info.positions = get_line_positions(num);
+                spawn_info.positions = get_line_positions(num, &spawn_info.points);
             }
             _ => {
                 // println!("Next is Pincer");
                 spawn_info.next_strat = SpawnStrat::Pincer;
-                spawn_info.positions = get_pincer_positions(num);
+                spawn_info.positions = get_pincer_positions(num, &spawn_info.points);
             }
         };
         ev_update.send(UpdateDirectorUiEvent);
@@ -193,24 +209,54 @@ fn update_dirctor_ui(ev_update: EventReader<UpdateDirectorUiEvent>, _spawn_info:
     }
 }
 
-fn get_spread_positions(num: u32) -> Vec<Vec2> {
+// a map can say where enemies come from
+fn setup_spawn_points(
+    mut spawn_info: ResMut<SpawnInfo>,
+    loaded: Res<LoadedMap>,
+    grid: Res<Grid>,
+    config: Res<GridConfig>,
+) {
+    spawn_info.points = match &loaded.0 {
+        Some(map) => map
+            .spawns
+            .iter()
+            .filter(|&&c| grid.contains(c))
+            .map(|&c| grid.tile_to_world(c))
+            .collect(),
+        None => Vec::new(),
+    };
+    let (x, y) = config.center();
+    spawn_info.castle = grid.tile_to_world(Coords::new(x as i32, y as i32));
+    spawn_info.positions = get_spread_positions(4, &spawn_info.points);
+}
+
+// one of the map's spawn points
+// or somewhere off the edge if there aren't any
+fn get_spawn_point(points: &[Vec2], rng: &mut ThreadRng) -> Vec2 {
+    match points.choose(rng) {
+        Some(&p) => p,
+        None => {
+            Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)).normalize_or_zero()
+                * 500.
+        }
+    }
+}
+
+fn get_spread_positions(num: u32, points: &[Vec2]) -> Vec<Vec2> {
     let mut v = Vec::new();
     let mut rng = rand::thread_rng();
     for _ in 0..num {
-        let spawn_pos = Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0))
-            .normalize_or_zero()
-            * 500.;
+        let spawn_pos = get_spawn_point(points, &mut rng);
         v.push(spawn_pos);
     }
     v
 }
 
-fn get_burst_positions(num: u32) -> Vec<Vec2> {
+fn get_burst_positions(num: u32, points: &[Vec2]) -> Vec<Vec2> {
     let mut v = Vec::new();
 
     let mut rng = rand::thread_rng();
-    let spawn_pos =
-        Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)).normalize_or_zero() * 500.;
+    let spawn_pos = get_spawn_point(points, &mut rng);
 
     for _ in 0..num {
         let offset = Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)) * 50.0;
@@ -219,14 +265,18 @@ fn get_burst_positions(num: u32) -> Vec<Vec2> {
     v
 }
 
-fn get_pincer_positions(num: u32) -> Vec<Vec2> {
+fn get_pincer_positions(num: u32, points: &[Vec2]) -> Vec<Vec2> {
     let mut v = Vec::new();
     let mut rng = rand::thread_rng();
 
-    let spawn_pos =
-        Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)).normalize_or_zero() * 500.;
+    let spawn_pos = get_spawn_point(points, &mut rng);
     // opposite side
-    let other_spawn = -spawn_pos; // Vec2::new(-spawn_pos.x, -spawn_pos.y);
+    // or another spawn point on a map
+    let other_spawn = if points.is_empty() {
+        -spawn_pos // Vec2::new(-spawn_pos.x, -spawn_pos.y);
+    } else {
+        get_spawn_point(points, &mut rng)
+    };
     let half_num = num / 2;
     let other_num = num - half_num;
     for _ in 0..half_num {
@@ -240,12 +290,11 @@ fn get_pincer_positions(num: u32) -> Vec<Vec2> {
     v
 }
 
-fn get_line_positions(num: u32) -> Vec<Vec2> {
+fn get_line_positions(num: u32, points: &[Vec2]) -> Vec<Vec2> {
     let mut v = Vec::new();
     let mut rng = rand::thread_rng();
 
-    let spawn_pos =
-        Vec2::new(rng.gen_range(-1.0..=1.0), rng.gen_range(-1.0..=1.0)).normalize_or_zero() * 500.;
+    let spawn_pos = get_spawn_point(points, &mut rng);
     let dir = (spawn_pos - Vec2::ZERO).normalize_or_zero();
     for i in 0..num {
         let offset = dir * 30.0 * i as f32;
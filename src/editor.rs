@@ -0,0 +1,152 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::{Board, NumberRule, Terrain, TileState, Topology},
+    grid::{tile_interaction, ClearSelectionsEvent, GridConfig, Selection, Tile},
+    map::{LoadedMap, MapFile, MapTile, MapTiles, USER_MAP_PATH},
+    pathfinding::PathSearch,
+    GameState,
+};
+
+// Tab on the menu opens the editor
+// select tiles like you would to build a tower, then press a number to paint them
+// Escape saves to the user map and goes back to the menu with the new map loaded
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(SystemSet::on_update(GameState::MainMenu).with_system(open_editor))
+            .add_system_set(SystemSet::on_enter(GameState::Editor).with_system(setup_editor))
+            .add_system_set(
+                SystemSet::on_update(GameState::Editor)
+                    .with_system(paint)
+                    .with_system(show_map_tiles.after(tile_interaction))
+                    .with_system(close_editor),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Editor).with_system(cleanup_editor));
+    }
+}
+
+const BRUSHES: [(KeyCode, MapTile); 10] = [
+    (KeyCode::Key1, MapTile::Wall),
+    (KeyCode::Key2, MapTile::Floor),
+    (KeyCode::Key3, MapTile::Mine),
+    (KeyCode::Key4, MapTile::Empty),
+    (KeyCode::Key5, MapTile::Water),
+    (KeyCode::Key6, MapTile::Castle),
+    (KeyCode::Key7, MapTile::Spawn),
+    (KeyCode::Key8, MapTile::Rock),
+    (KeyCode::Key9, MapTile::HighGround),
+    (KeyCode::Key0, MapTile::Swamp),
+];
+
+fn open_editor(keyboard: Res<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    if keyboard.just_pressed(KeyCode::Tab) {
+        state.set(GameState::Editor).unwrap();
+    }
+}
+
+// start from the map that's loaded, or a blank one the size of the board
+fn setup_editor(mut commands: Commands, loaded: Res<LoadedMap>, config: Res<GridConfig>) {
+    let tiles = match &loaded.0 {
+        Some(map) => MapTiles::from_map(map),
+        None => MapTiles::blank(config.width, config.height),
+    };
+    println!("Editor: 1 wall, 2 floor, 3 mine, 4 empty, 5 water, 6 castle, 7 spawn, 8 rock, 9 high ground, 0 swamp. Esc saves");
+    commands.insert_resource(tiles);
+}
+
+fn paint(
+    keyboard: Res<Input<KeyCode>>,
+    q_selection: Query<&Tile, With<Selection>>,
+    mut tiles: ResMut<MapTiles>,
+    mut ev_clear: EventWriter<ClearSelectionsEvent>,
+) {
+    for (key, brush) in BRUSHES {
+        if !keyboard.just_pressed(key) {
+            continue;
+        }
+        for tile in q_selection.iter() {
+            tiles.set(tile.coords(), brush);
+        }
+        ev_clear.send(ClearSelectionsEvent);
+    }
+}
+
+// runs after tile_interaction so it draws over the board colours
+fn show_map_tiles(mut q_tiles: Query<(&Interaction, &mut Sprite, &Tile)>, tiles: Res<MapTiles>) {
+    for (interaction, mut sprite, tile) in q_tiles.iter_mut() {
+        if *interaction != Interaction::None {
+            continue;
+        }
+        if let Some(map_tile) = tiles.get(tile.coords()) {
+            sprite.color = match map_tile {
+                MapTile::Wall | MapTile::Rock | MapTile::HighGround | MapTile::Swamp => {
+                    tile.get_colour(Some(TileState::Wall), map_tile.terrain())
+                }
+                MapTile::Floor => tile.get_colour(Some(TileState::Floor), Terrain::Plain),
+                MapTile::Water => tile.get_colour(Some(TileState::Blocked), Terrain::Water),
+                // #e43b44
+                MapTile::Mine => Color::rgb_u8(0xe4, 0x3b, 0x44),
+                // #8b9bb4
+                MapTile::Empty => Color::rgb_u8(0x8b, 0x9b, 0xb4),
+                // #feae34
+                MapTile::Castle => Color::rgb_u8(0xfe, 0xae, 0x34),
+                // #b55088
+                MapTile::Spawn => Color::rgb_u8(0xb5, 0x50, 0x88),
+            };
+        }
+    }
+}
+
+fn close_editor(
+    keyboard: Res<Input<KeyCode>>,
+    tiles: Res<MapTiles>,
+    mut loaded: ResMut<LoadedMap>,
+    mut config: ResMut<GridConfig>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if !keyboard.just_pressed(KeyCode::Escape) {
+        return;
+    }
+    let text = format!("// made in the editor\n{}", tiles.to_text());
+    // parse it back so a broken map never gets saved
+    let map = match MapFile::parse(&text) {
+        Ok(map) => map,
+        Err(e) => {
+            println!("Map not saved: {}", e);
+            return;
+        }
+    };
+    warn_unreachable_spawns(&map, config.topology);
+    // M on the menu loads this instead of the shipped level next time
+    match std::fs::write(USER_MAP_PATH, text) {
+        Ok(_) => println!("Map saved to {}", USER_MAP_PATH),
+        // still play it, it just won't be there next time
+        Err(e) => println!("Couldn't write {}: {}", USER_MAP_PATH, e),
+    }
+    config.castle = Some((map.castle.x as usize, map.castle.y as usize));
+    loaded.0 = Some(map);
+    state.set(GameState::MainMenu).unwrap();
+}
+
+// enemies dig through walls, but water cuts a spawn off for good
+// saved anyway, it might not be finished
+fn warn_unreachable_spawns(map: &MapFile, topology: Topology) {
+    let mut board = Board::new(map.width, map.height, topology, NumberRule::Adjacent);
+    board.load_map(map);
+    let mut search = PathSearch::default();
+    for &spawn in map.spawns.iter() {
+        if search.find_path(&board, spawn, map.castle).is_none() {
+            println!("Spawn at {}, {} can't reach the castle", spawn.x, spawn.y);
+        }
+    }
+}
+
+// the menu makes the grid again from the new map
+fn cleanup_editor(mut commands: Commands, q_tiles: Query<Entity, With<Tile>>) {
+    for entity in q_tiles.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<MapTiles>();
+}
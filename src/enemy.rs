@@ -2,7 +2,10 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 
 use crate::{
+    board::Board,
     castle::Castle,
+    flow_field::FlowField,
+    grid::Grid,
     loading::SpriteAssets,
     tower::{Movement, Target},
 };
@@ -37,6 +40,8 @@ impl Enemy {
 pub fn spawn_enemy(
     commands: &mut Commands,
     position: Vec3,
+    // where the castle is
+    target: Vec2,
     health: u32,
     textures: &Res<SpriteAssets>,
 ) {
@@ -53,7 +58,7 @@ pub fn spawn_enemy(
         })
         .insert(Enemy::new(health))
         .insert(Movement {
-            target: Target::Point(Some(Vec3::ZERO)),
+            target: Target::Point(Some(target.extend(position.z))),
             speed: 50.0,
         })
         .insert(Collider::cuboid(10.0, 10.0))
@@ -61,15 +66,52 @@ pub fn spawn_enemy(
         .insert(RigidBody::Dynamic);
 }
 
-fn movement(mut q_enemies: Query<(&mut Transform, &Movement, &Enemy)>, time: Res<Time>) {
-    for (mut trans, movement, _enemy) in q_enemies.iter_mut() {
+fn movement(
+    mut q_enemies: Query<(&mut Transform, &Movement), With<Enemy>>,
+    time: Res<Time>,
+    grid: Res<Grid>,
+    board: Res<Board>,
+    flow_field: Res<FlowField>,
+) {
+    // None can't be walked on. Off the board is open ground
+    let cost_at = |pos: Vec3| -> Option<u32> {
+        match grid.world_to_tile(pos.truncate()) {
+            Some(c) => board.movement_cost(c),
+            None => Some(1),
+        }
+    };
+
+    for (mut trans, movement) in q_enemies.iter_mut() {
         match movement.target {
             Target::None => todo!(),
             Target::Point(p) => {
                 if let Some(p) = p {
-                    let dir = p - trans.translation;
-                    trans.translation +=
-                        dir.normalize_or_zero() * time.delta_seconds() * movement.speed;
+                    // follow the flow field, then straight at the target
+                    // off the board there's nothing in the way
+                    let goal = match grid
+                        .world_to_tile(trans.translation.truncate())
+                        .and_then(|c| flow_field.next_tile(&*board, c))
+                    {
+                        Some(c) => grid.tile_to_world(c).extend(p.z),
+                        None => p,
+                    };
+                    let dir = goal - trans.translation;
+                    // rock and swamp slow them down
+                    let cost = cost_at(trans.translation).unwrap_or(1);
+                    let step = dir.normalize_or_zero() * time.delta_seconds() * movement.speed
+                        / cost as f32;
+                    // slide along water instead of stopping dead
+                    let steps = [
+                        step,
+                        Vec3::new(step.x, 0.0, 0.0),
+                        Vec3::new(0.0, step.y, 0.0),
+                    ];
+                    if let Some(step) = steps
+                        .into_iter()
+                        .find(|&s| cost_at(trans.translation + s).is_some())
+                    {
+                        trans.translation += step;
+                    }
                 }
             }
             // Target::Follow(_) => todo!(),
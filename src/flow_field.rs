@@ -1,222 +1,350 @@
-use bevy::{math::vec2, prelude::*};
-use rand::prelude::*;
+use bevy::prelude::*;
+use std::{cmp::Reverse, collections::BinaryHeap, collections::HashSet};
 
+use crate::{
+    board::{Board, Coords},
+    castle::TerritoryInfo,
+    graph::GridGraph,
+    GameState,
+};
+
+// every tile on the board knows which way is quickest to the castle
+// enemies just look up the tile they're on
+// one search for the whole horde instead of one each
 pub struct FlowFieldPlugin;
 
 impl Plugin for FlowFieldPlugin {
     fn build(&self, app: &mut App) {
-        app.add_startup_system(test_flow);
+        app.init_resource::<FlowField>()
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(build_flow_field))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(update_flow_field),
+            );
     }
 }
 
 #[derive(Copy, Clone, Debug)]
-struct Tile {
-    cost: u8,
+struct FlowTile {
+    // cost to get from here to the destination
+    // u32::MAX if it can't get there
     weight: u32,
-    dir: Option<Vec2>,
+    // the neighbour to walk to
+    next: Option<Coords>,
+    // the graph's cost when the weight was worked out
+    // if the graph doesn't match anymore, the tile changed
+    cost: Option<u32>,
+}
+
+impl FlowTile {
+    fn new() -> Self {
+        FlowTile {
+            weight: u32::MAX,
+            next: None,
+            cost: None,
+        }
+    }
 }
 
-fn test_flow() {
-    generate_flow_field(2, 2);
+// resource
+#[derive(Default)]
+pub struct FlowField {
+    // indexed by GridGraph::index
+    tiles: Vec<FlowTile>,
+    destination: Option<Coords>,
+    // how much of the graph's cost_changes has been looked at
+    changes_seen: usize,
 }
 
-fn generate_flow_field(destination_x: usize, destination_y: usize) {
-    let width = 10;
-    let height = 8;
-
-    let mut nodes: Vec<Tile> = Vec::with_capacity(width * height);
-    // create the tiles
-    for _i in 0..width {
-        for _j in 0..height {
-            let mut rng = rand::thread_rng();
-
-            nodes.push(Tile {
-                cost: if rng.gen_bool(0.5) { 1 } else { 10 },
-                weight: u32::MAX,
-                dir: None,
-            });
-
-            // let index = calculate_index(i, j, height);
-            // let x = index / height;
-            // let y = index % height;
-            // println!("i,j: ({}, {}) index: {} back: ({}, {})", i, j, index, x, y);
+impl FlowField {
+    pub fn new<G: GridGraph>(graph: &G, destination: Coords) -> Self {
+        let mut tiles = vec![FlowTile::new(); graph.index_len()];
+        for (i, tile) in tiles.iter_mut().enumerate() {
+            tile.cost = graph.coords(i).and_then(|c| graph.cost(c));
+        }
+        let mut flow_field = FlowField {
+            tiles,
+            destination: Some(destination),
+            changes_seen: graph.cost_changes().len(),
+        };
+
+        let mut open_set = BinaryHeap::new();
+        if let Some(i) = graph.index(destination) {
+            flow_field.tiles[i].weight = 0;
+            open_set.push(Reverse((0, destination)));
         }
+        flow_field.spread(graph, open_set);
+        flow_field
     }
 
-    let destination_index = calculate_index(destination_x, destination_y, height);
-    let mut dest_node = nodes.get_mut(destination_index).unwrap();
-    dest_node.weight = 0;
+    fn tile<G: GridGraph>(&self, graph: &G, coords: Coords) -> &FlowTile {
+        &self.tiles[graph.index(coords).unwrap()]
+    }
 
-    let mut open_set = Vec::new();
-    let mut closed_set = Vec::new();
+    fn tile_mut<G: GridGraph>(&mut self, graph: &G, coords: Coords) -> &mut FlowTile {
+        &mut self.tiles[graph.index(coords).unwrap()]
+    }
+
+    // the tile to head for from here
+    // None at the destination, off the graph, or if there's no way there
+    pub fn next_tile<G: GridGraph>(&self, graph: &G, coords: Coords) -> Option<Coords> {
+        self.tiles.get(graph.index(coords)?)?.next
+    }
 
-    open_set.push(destination_index);
+    // only works out the tiles that changed since last time
+    // and the ones whose way to the destination went through them
+    // ends up the same as building a new one
+    pub fn update<G: GridGraph>(&mut self, graph: &G, destination: Coords) {
+        // a different castle means starting over
+        // so does a shorter change list, it's a different graph
+        let changes = graph.cost_changes();
+        if self.destination != Some(destination) || self.changes_seen > changes.len() {
+            *self = FlowField::new(graph, destination);
+            return;
+        }
+
+        // only the tiles the graph says changed, and any new ones
+        // new tiles start with no cost, so they count as changed
+        let old_len = self.tiles.len();
+        self.tiles.resize(graph.index_len(), FlowTile::new());
+        let changed: Vec<Coords> = changes[self.changes_seen..]
+            .iter()
+            .copied()
+            .chain((old_len..graph.index_len()).filter_map(|i| graph.coords(i)))
+            .filter(|&c| self.tile(graph, c).cost != graph.cost(c))
+            .collect();
+        self.changes_seen = changes.len();
+        if changed.is_empty() {
+            return;
+        }
 
-    while open_set.len() > 0 {
-        // get the lowest weight in the open_set
-        if let Some(current_node_index) = get_lowest_weight_index(&open_set, &nodes) {
-            // remove from the open list
-            for (i, val) in open_set.iter().enumerate() {
-                if *val == current_node_index {
-                    open_set.remove(i);
-                    break;
+        // everything that walked through a changed tile has to look again
+        let mut lost: HashSet<Coords> = changed.iter().copied().collect();
+        let mut stack = changed;
+        while let Some(current) = stack.pop() {
+            for n in graph.neighbours(current) {
+                if self.tile(graph, n).next == Some(current) && lost.insert(n) {
+                    stack.push(n);
                 }
             }
+        }
+        for &c in lost.iter() {
+            let cost = graph.cost(c);
+            let tile = self.tile_mut(graph, c);
+            tile.weight = u32::MAX;
+            tile.next = None;
+            tile.cost = cost;
+        }
 
-            // add self to closed set so it isn't checked again
-            closed_set.push(current_node_index);
+        // start them off from whichever neighbour still knows the way
+        let mut open_set = BinaryHeap::new();
+        for &c in lost.iter() {
+            if c == destination {
+                self.tile_mut(graph, c).weight = 0;
+                open_set.push(Reverse((0, c)));
+                continue;
+            }
+            if !graph.is_walkable(c) {
+                continue;
+            }
+            let best = graph
+                .neighbours(c)
+                .filter(|n| !lost.contains(n))
+                .filter_map(|n| {
+                    let n_tile = self.tile(graph, n);
+                    let enter_cost = n_tile.cost?;
+                    (n_tile.weight != u32::MAX)
+                        .then(|| (n_tile.weight + graph.step_cost(c, n) * enter_cost, n))
+                })
+                .min();
+            if let Some((weight, n)) = best {
+                let tile = self.tile_mut(graph, c);
+                tile.weight = weight;
+                tile.next = Some(n);
+                open_set.push(Reverse((weight, c)));
+            }
+        }
+        // a cheaper tile can make the tiles around it cheaper too
+        // spread handles that, it doesn't stop at the lost ones
+        self.spread(graph, open_set);
+    }
 
-            // get neighbours (4-connected)
-            let neighbours = get_neighbour_indicies(current_node_index, width, height, false);
-            let current_node = nodes.get(current_node_index).unwrap().clone();
+    // dijkstra outwards from the tiles in the open set
+    // a tile only changes if it gets cheaper
+    fn spread<G: GridGraph>(
+        &mut self,
+        graph: &G,
+        mut open_set: BinaryHeap<Reverse<(u32, Coords)>>,
+    ) {
+        while let Some(Reverse((weight, current))) = open_set.pop() {
+            // already got here a cheaper way
+            if weight > self.tile(graph, current).weight {
+                continue;
+            }
 
-            for n_index in neighbours {
-                // skip neighbours that have already been checked
-                if closed_set.contains(&n_index) {
+            // everyone coming through here pays to step onto it
+            let enter_cost = graph.cost(current).unwrap_or(1);
+            for n in graph.neighbours(current) {
+                // towers and water can't be stood on
+                if !graph.is_walkable(n) {
                     continue;
                 }
 
-                if let Some(neighbour_node) = nodes.get_mut(n_index) {
-                    // update the new weight
-                    // unless it's already lower bc it was evaluated by another neighbour
-                    let tentative_weight = current_node.weight + neighbour_node.cost as u32;
-
-                    if tentative_weight < neighbour_node.weight {
-                        neighbour_node.weight = tentative_weight;
-
-                        // add the neighbour to the open set to be evaluated later
-                        if !open_set.contains(&n_index) {
-                            open_set.push(n_index);
-                        }
-                    }
+                let tentative_weight = weight + graph.step_cost(n, current) * enter_cost;
+                let neighbour_node = self.tile_mut(graph, n);
+                if tentative_weight < neighbour_node.weight {
+                    neighbour_node.weight = tentative_weight;
+                    // the way back is the way to the destination
+                    neighbour_node.next = Some(current);
+                    open_set.push(Reverse((tentative_weight, n)));
                 }
             }
         }
     }
+}
 
-    // calculate dir
-    // get 8 neighbours
-    // get the lowest weight among them
-    // that is the neighbour to move towards
-    // your dir is them - you
-    for i in 0..nodes.len() {
-        let neighbours = get_neighbour_indicies(i, width, height, true);
-
-        let smallest_index = neighbours
-            .iter()
-            .min_by_key(|x| nodes.get(**x).unwrap().weight)
-            .copied()
-            .unwrap();
+fn build_flow_field(
+    mut flow_field: ResMut<FlowField>,
+    board: Res<Board>,
+    territory_info: Res<TerritoryInfo>,
+) {
+    let castle = Coords::new(territory_info.x as i32, territory_info.y as i32);
+    *flow_field = FlowField::new(&*board, castle);
+}
 
-        let mut val = nodes.get_mut(i).unwrap();
-        if val.weight == 0 {
-            val.dir = Some(Vec2::ZERO);
-        } else {
-            let small_x = smallest_index / height;
-            let small_y = smallest_index % height;
+// digging, towers and the territory growing all change the board
+fn update_flow_field(
+    mut flow_field: ResMut<FlowField>,
+    board: Res<Board>,
+    territory_info: Res<TerritoryInfo>,
+) {
+    if !board.is_changed() && !territory_info.is_changed() {
+        return;
+    }
+    let castle = Coords::new(territory_info.x as i32, territory_info.y as i32);
+    flow_field.update(&*board, castle);
+}
 
-            let my_x = i / height;
-            let my_y = i % height;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::{ExpansionRule, NumberRule, RingShape, Terrain, Topology};
+    use rand::{rngs::StdRng, Rng, SeedableRng};
 
-            let dir = vec2(small_x as f32 - my_x as f32, small_y as f32 - my_y as f32);
-            val.dir = Some(dir);
+    fn assert_same(board: &Board, updated: &FlowField, rebuilt: &FlowField) {
+        for (c, _) in board.iter() {
+            let tile = updated.tile(board, c);
+            assert_eq!(
+                tile.weight,
+                rebuilt.tile(board, c).weight,
+                "weight at {:?}",
+                c
+            );
+            // ties can point different ways, but it has to cost what it says
+            match tile.next {
+                Some(n) => {
+                    let n_tile = updated.tile(board, n);
+                    let step = board.step_cost(c, n) * board.path_cost(n).unwrap();
+                    assert_eq!(tile.weight, n_tile.weight + step, "next at {:?}", c);
+                }
+                None => assert!(tile.weight == 0 || tile.weight == u32::MAX),
+            }
         }
     }
 
-    // for (i, val) in nodes.iter().enumerate() {
-    //     // ><^v /\
-    //     println!("{}: {:?}", i, val);
-    // }
-    for j in 0..height {
-        for i in 0..width {
-            let mut c = "   ";
-            match nodes.get(calculate_index(i, height - (j + 1), height)).unwrap().dir {
-                Some(v) => {
-                    let x = v.x.floor();
-                    let y = v.y.floor();
-                    if x == 0.0 && y == 1.0 {
-                        c = " ^ ";
-                    } else if x == 1.0 && y == 1.0 {
-                        c = " / ";
-                    } else if x == 1.0 && y == 0.0 {
-                        c = " > ";
-                    } else if x == 1.0 && y == -1.0 {
-                        c = " \\ ";
-                    } else if x == 0.0 && y == -1.0 {
-                        c = " v ";
-                    } else if x == -1.0 && y == -1.0 {
-                        c = " / ";
-                    } else if x == -1.0 && y == 0.0 {
-                        c = " < ";
-                    } else if x == -1.0 && y == 1.0 {
-                        c = " \\ ";
-                    }
-                }
+    // random towers, digging and terrain, checked against a full rebuild each time
+    fn random_changes(topology: Topology, seed: u64) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut board = Board::new(20, 16, topology, NumberRule::Adjacent);
+        let castle = Coords::new(10, 8);
+        board.reveal(castle);
+        let mut flow_field = FlowField::new(&board, castle);
 
-                None => {
-                    c = " ";
+        for _ in 0..100 {
+            let c = Coords::new(rng.gen_range(0..20), rng.gen_range(0..16));
+            if c == castle {
+                continue;
+            }
+            match rng.gen_range(0..5) {
+                0 | 1 => {
+                    let _ = board.try_place_tower(c);
                 }
-            };
-            print!("{}", c);
+                2 => {
+                    board.reveal(c);
+                }
+                3 => board.set_terrain(c, Terrain::Swamp),
+                _ => board.set_terrain(c, Terrain::Water),
+            }
+            flow_field.update(&board, castle);
+            assert_same(&board, &flow_field, &FlowField::new(&board, castle));
         }
-        println!("");
     }
-}
-
-fn get_neighbour_indicies(
-    index: usize,
-    width: usize,
-    height: usize,
-    eight_connected: bool,
-) -> Vec<usize> {
-    let mut v = Vec::new();
 
-    let x = index / height;
-    let y = index % height;
-
-    if y < height - 1 {
-        // up
-        v.push(calculate_index(x, y + 1, height));
-    }
-    if eight_connected && y < height - 1 && x < width - 1 {
-        // up right
-        v.push(calculate_index(x + 1, y + 1, height));
-    }
-    if x < width - 1 {
-        // right
-        v.push(calculate_index(x + 1, y, height));
-    }
-    if eight_connected && y > 0 && x < width - 1 {
-        // down right
-        v.push(calculate_index(x + 1, y - 1, height));
-    }
-    if y > 0 {
-        // down
-        v.push(calculate_index(x, y - 1, height));
-    }
-    if eight_connected && y > 0 && x > 0 {
-        // down left
-        v.push(calculate_index(x - 1, y - 1, height));
-    }
-    if x > 0 {
-        // left
-        v.push(calculate_index(x - 1, y, height));
+    #[test]
+    fn square_matches_rebuild() {
+        for seed in 0..10 {
+            random_changes(Topology::Square, seed);
+        }
     }
-    if eight_connected && x > 0 && y < height - 1 {
-        // up left
-        v.push(calculate_index(x - 1, y + 1, height));
+
+    #[test]
+    fn hex_matches_rebuild() {
+        for seed in 0..10 {
+            random_changes(Topology::Hex, seed);
+        }
     }
 
-    v
-}
+    // new chunks aren't in the change list, but still have to be picked up
+    #[test]
+    fn endless_board_grows() {
+        let castle = Coords::new(0, 0);
+        let mut board = Board::endless(castle, Topology::Square, NumberRule::Adjacent);
+        let mut flow_field = FlowField::new(&board, castle);
+        let mut rng = StdRng::seed_from_u64(0);
+        let before = board.index_len();
+        for radius in [1, 5, 9, 13, 17] {
+            let info = TerritoryInfo {
+                radius,
+                x: 0,
+                y: 0,
+                bombs_percent: 0.3,
+                expansion: ExpansionRule::Square,
+                shape: RingShape::Square,
+                no_guess: false,
+            };
+            board.expand(&info, &mut rng);
+            flow_field.update(&board, castle);
+            assert_same(&board, &flow_field, &FlowField::new(&board, castle));
+        }
+        assert!(board.index_len() > before);
+    }
 
-fn get_lowest_weight_index(open_set: &[usize], nodes: &[Tile]) -> Option<usize> {
-    open_set
-        .iter()
-        .min_by_key(|x| nodes.get(**x).unwrap().weight)
-        .copied()
-}
+    #[test]
+    fn walled_off() {
+        let mut board = Board::new(5, 5, Topology::Square, NumberRule::Adjacent);
+        let castle = Coords::new(0, 0);
+        let mut flow_field = FlowField::new(&board, castle);
+        // a row of towers across the board cuts the top off
+        for x in 0..5 {
+            board.try_place_tower(Coords::new(x, 2)).unwrap();
+        }
+        flow_field.update(&board, castle);
+        assert_same(&board, &flow_field, &FlowField::new(&board, castle));
+        assert_eq!(flow_field.next_tile(&board, Coords::new(4, 4)), None);
+    }
 
-fn calculate_index(x: usize, y: usize, height: usize) -> usize {
-    y + x * height
+    #[test]
+    fn new_destination() {
+        let board = Board::new(6, 6, Topology::Square, NumberRule::Adjacent);
+        let mut flow_field = FlowField::new(&board, Coords::new(0, 0));
+        flow_field.update(&board, Coords::new(5, 5));
+        assert_same(
+            &board,
+            &flow_field,
+            &FlowField::new(&board, Coords::new(5, 5)),
+        );
+        assert_eq!(
+            flow_field.next_tile(&board, Coords::new(4, 4)),
+            Some(Coords::new(5, 5))
+        );
+    }
 }
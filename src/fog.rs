@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+use crate::{
+    board::{Board, Coords},
+    castle::TerritoryInfo,
+    enemy::Enemy,
+    grid::{Grid, GridConfig, Tile},
+    tower::Tower,
+    GameState,
+};
+
+// you can see your territory and as far as your towers reach
+// everything else is under fog. Enemies out there are hidden
+pub struct FogPlugin;
+
+impl Plugin for FogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Fog>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(spawn_fog)
+                .with_system(update_fog)
+                .with_system(show_fog.after(update_fog))
+                .with_system(hide_enemies.after(update_fog)),
+        );
+    }
+}
+
+// resource
+// the tiles that can be seen
+#[derive(Default)]
+pub struct Fog {
+    clear: HashSet<Coords>,
+}
+
+impl Fog {
+    // off the board is always fogged
+    pub fn is_clear(&self, coords: Coords) -> bool {
+        self.clear.contains(&coords)
+    }
+}
+
+// Components
+// the dark square on top of a tile
+#[derive(Component)]
+struct FogSprite;
+
+// on a tile that has its FogSprite
+#[derive(Component)]
+struct Fogged;
+
+// endless boards keep spawning tiles, so this keeps looking for new ones
+fn spawn_fog(
+    mut commands: Commands,
+    config: Res<GridConfig>,
+    q_tiles: Query<Entity, (With<Tile>, Without<Fogged>)>,
+) {
+    for tile in q_tiles.iter() {
+        let child = commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    // #181425
+                    color: Color::rgba_u8(0x18, 0x14, 0x25, 0xaa),
+                    custom_size: Some(Vec2::splat(config.tile_size)),
+                    ..default()
+                },
+                // over the numbers and the castle
+                transform: Transform::from_xyz(0.0, 0.0, 0.4),
+                ..default()
+            })
+            .insert(FogSprite)
+            .id();
+        commands.entity(tile).insert(Fogged).add_child(child);
+    }
+}
+
+// a tile is clear if it's in the territory, already dug out,
+// or close enough to a tower
+fn update_fog(
+    mut fog: ResMut<Fog>,
+    board: Res<Board>,
+    territory_info: Res<TerritoryInfo>,
+    grid: Res<Grid>,
+    q_tiles: Query<&Tile>,
+    q_towers: Query<(&Tower, &Parent)>,
+    q_new_towers: Query<(), Added<Tower>>,
+) {
+    if !board.is_changed() && !territory_info.is_changed() && q_new_towers.is_empty() {
+        return;
+    }
+    fog.clear.clear();
+    // towers sit on a tile
+    let sight: Vec<(Vec2, f32)> = q_towers
+        .iter()
+        .filter_map(|(tower, parent)| {
+            let tile = q_tiles.get(parent.get()).ok()?;
+            Some((grid.tile_to_world(tile.coords()), tower.range))
+        })
+        .collect();
+    for tile in q_tiles.iter() {
+        let c = tile.coords();
+        // seen if the range reaches any part of the tile
+        let (min, max) = grid.tile_bounds(c);
+        let dug = board.get(c).is_some_and(|t| !t.state.is_wall());
+        let seen = dug
+            || board.in_territory(&territory_info, c)
+            || sight
+                .iter()
+                .any(|&(tower, range)| tower.clamp(min, max).distance(tower) <= range);
+        if seen {
+            fog.clear.insert(c);
+        }
+    }
+}
+
+fn show_fog(
+    fog: Res<Fog>,
+    q_tiles: Query<&Tile>,
+    mut q_fog: Query<(&Parent, &mut Visibility), With<FogSprite>>,
+) {
+    // not just when the fog changes, the sprites can show up a frame later
+    for (parent, mut visibility) in q_fog.iter_mut() {
+        if let Ok(tile) = q_tiles.get(parent.get()) {
+            visibility.is_visible = !fog.is_clear(tile.coords());
+        }
+    }
+}
+
+fn hide_enemies(
+    fog: Res<Fog>,
+    grid: Res<Grid>,
+    mut q_enemies: Query<(&Transform, &mut Visibility), With<Enemy>>,
+) {
+    for (trans, mut visibility) in q_enemies.iter_mut() {
+        visibility.is_visible = grid
+            .world_to_tile(trans.translation.truncate())
+            .is_some_and(|c| fog.is_clear(c));
+    }
+}
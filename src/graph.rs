@@ -0,0 +1,130 @@
+use crate::board::{Coords, Topology};
+
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+
+// clockwise from straight up
+const SQUARE_NEIGHBOURS: [(i32, i32); 8] = [
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+    (-1, 0),
+    (-1, 1),
+];
+// hex boards keep the same x, y as square ones
+// odd rows are pushed half a tile right, so the diagonals lean that way
+// the same index is the same direction in both
+const HEX_NEIGHBOURS_ODD: [(i32, i32); 6] = [(0, 1), (1, 1), (1, 0), (1, -1), (0, -1), (-1, 0)];
+const HEX_NEIGHBOURS_EVEN: [(i32, i32); 6] = [(-1, 1), (0, 1), (1, 0), (0, -1), (-1, -1), (-1, 0)];
+
+// what to add to coords to get each tile next to it
+// on or off the board
+pub fn neighbour_offsets(topology: Topology, coords: Coords) -> &'static [(i32, i32)] {
+    match topology {
+        Topology::Square => &SQUARE_NEIGHBOURS,
+        Topology::Hex if coords.y.rem_euclid(2) == 1 => &HEX_NEIGHBOURS_ODD,
+        Topology::Hex => &HEX_NEIGHBOURS_EVEN,
+    }
+}
+
+// tiles laid out in a grid that can be walked across
+// A*, the flow field and the minesweeper rings all go through this
+// so a new topology or storage only has to be done once
+pub trait GridGraph {
+    fn topology(&self) -> Topology;
+
+    // is the tile part of the graph
+    fn contains(&self, coords: Coords) -> bool;
+
+    // what it costs to step onto a tile
+    // None if it can't be walked on
+    fn cost(&self, coords: Coords) -> Option<u32>;
+
+    // a number for every tile, so searches can use flat Vecs
+    // the graph growing doesn't change the old numbers
+    fn index(&self, coords: Coords) -> Option<usize>;
+
+    fn coords(&self, index: usize) -> Option<Coords>;
+
+    // one more than the biggest index
+    fn index_len(&self) -> usize;
+
+    // tiles whose cost might have changed, oldest first
+    // it only grows, so a flow field can carry on from where it last read
+    fn cost_changes(&self) -> &[Coords];
+
+    fn is_walkable(&self, coords: Coords) -> bool {
+        self.cost(coords).is_some()
+    }
+
+    // the tiles next to coords that are on the graph
+    fn neighbours(&self, coords: Coords) -> Neighbours<'_, Self>
+    where
+        Self: Sized,
+    {
+        Neighbours {
+            graph: self,
+            center: coords,
+            offsets: neighbour_offsets(self.topology(), coords).iter(),
+        }
+    }
+
+    // the tiles radius steps away that are on the graph
+    fn ring(&self, coords: Coords, radius: i32) -> Vec<Coords> {
+        self.topology()
+            .ring(coords, radius)
+            .into_iter()
+            .filter(|&c| self.contains(c))
+            .collect()
+    }
+
+    // between two neighbours, before the cost of the tile
+    fn step_cost(&self, a: Coords, b: Coords) -> u32 {
+        match self.topology() {
+            Topology::Square if a.x != b.x && a.y != b.y => DIAGONAL_COST,
+            _ => STRAIGHT_COST,
+        }
+    }
+
+    // the cheapest a walk from a to b could be
+    // never more than the real cost, so A* can use it
+    fn distance_cost(&self, a: Coords, b: Coords) -> u32 {
+        // (0,1) -> (3,1)
+        // 3, 0
+        // r = 3
+        // 0*14 + 3*10 = 30
+        match self.topology() {
+            Topology::Square => {
+                let x_dist = a.x.abs_diff(b.x);
+                let y_dist = a.y.abs_diff(b.y);
+                let remainder = x_dist.abs_diff(y_dist);
+                DIAGONAL_COST * x_dist.min(y_dist) + STRAIGHT_COST * remainder
+            }
+            // every step is straight
+            Topology::Hex => STRAIGHT_COST * a.hex_distance(b) as u32,
+        }
+    }
+}
+
+pub struct Neighbours<'a, G> {
+    graph: &'a G,
+    center: Coords,
+    offsets: std::slice::Iter<'static, (i32, i32)>,
+}
+
+impl<'a, G: GridGraph> Iterator for Neighbours<'a, G> {
+    type Item = Coords;
+
+    fn next(&mut self) -> Option<Coords> {
+        for &(dx, dy) in self.offsets.by_ref() {
+            let c = Coords::new(self.center.x + dx, self.center.y + dy);
+            if self.graph.contains(c) {
+                return Some(c);
+            }
+        }
+        None
+    }
+}
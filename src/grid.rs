@@ -1,8 +1,14 @@
 use bevy::{prelude::*, render::texture::ImageSettings};
-use std::collections::HashSet;
+use rand::prelude::*;
 
 use crate::{
-    castle::{ExpandAreaEvent, NumberFilledEvent, TerritoryInfo},
+    board::{
+        Board, Contradiction, Coords, ExpansionRule, NumberRule, Terrain, TileState, Topology,
+    },
+    castle::{ChordEvent, ExpandAreaEvent, NumberFilledEvent, TerritoryInfo},
+    chunk::{self, Chunks},
+    loading::{FontAssets, MapAsset, MapAssets, SpriteAssets},
+    map::{read_user_map, LoadedMap, MapFile},
     tower::TowerPlacedEvent,
     GameState, MouseWorldPos,
 };
@@ -12,15 +18,32 @@ pub struct GridPlugin;
 
 impl Plugin for GridPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Grid { tiles: Vec::new() })
+        // setup_grid rebuilds these from whatever GridConfig is in use
+        let config = GridConfig::default();
+        app.init_resource::<GridConfig>()
+            .init_resource::<MineRng>()
+            .init_resource::<LoadedMap>()
+            .insert_resource(Grid::new(&config, false))
+            .insert_resource(new_board(&config, None, 0))
             .insert_resource(ImageSettings::default_nearest())
             .insert_resource(NumberTextures::default())
-            .add_event::<ClearSelectionsEvent>();
+            .add_event::<ClearSelectionsEvent>()
+            .add_event::<FlagEvent>()
+            .add_event::<MisplacedTowerEvent>()
+            .add_event::<NumberContradictionEvent>();
 
         app.add_system_set(SystemSet::on_enter(GameState::Loading).with_system(setup_atlas))
             .add_system_set(
                 SystemSet::on_enter(GameState::MainMenu).with_system(setup_grid), //.after(setup_atlas)
             )
+            .add_system_set(
+                SystemSet::on_update(GameState::MainMenu)
+                    .with_system(cycle_topology)
+                    .with_system(cycle_number_rule)
+                    .with_system(toggle_terrain)
+                    .with_system(toggle_endless)
+                    .with_system(toggle_map),
+            )
             // .add_system_set(
             //     // after setup_atlas
             //     SystemSet::on_enter(GameState::Playing).with_system(setup),
@@ -29,12 +52,24 @@ impl Plugin for GridPlugin {
             .add_system_set(
                 SystemSet::on_update(GameState::Playing)
                     .with_system(expand_floor)
+                    .with_system(spawn_new_chunks)
+                    .with_system(chord.after(interaction))
                     .with_system(clear_interaction)
                     .with_system(interaction.after(clear_interaction))
                     .with_system(tile_interaction.after(interaction))
                     .with_system(clear_selection.after(tile_interaction))
                     .with_system(update_numbers)
-                    .with_system(decrement_numbers),
+                    .with_system(toggle_flags.after(interaction))
+                    .with_system(update_flags.after(toggle_flags))
+                    .with_system(count_towers),
+            )
+            // the editor picks tiles the same way
+            .add_system_set(
+                SystemSet::on_update(GameState::Editor)
+                    .with_system(clear_interaction)
+                    .with_system(interaction.after(clear_interaction))
+                    .with_system(tile_interaction.after(interaction))
+                    .with_system(clear_selection.after(tile_interaction)),
             );
         // exit
         // .add_system_set(SystemSet::on_exit(GameState::MainMenu).with_system(cleanup_menu));
@@ -55,13 +90,99 @@ impl Plugin for GridPlugin {
     }
 }
 
-const GRID_WIDTH: usize = 21;
-const GRID_HEIGHT: usize = 21;
-const TILE_SIZE: f32 = 30.0;
+// resource
+// size of the board for this run
+// insert a different one before GamePlugin for smaller or larger boards
+pub struct GridConfig {
+    pub width: usize,
+    pub height: usize,
+    pub tile_size: f32,
+    // replay a board. None picks a new seed
+    pub seed: Option<u64>,
+    pub topology: Topology,
+    // which tiles the numbers count
+    pub number_rule: NumberRule,
+    // None is the middle of the board
+    pub castle: Option<(usize, usize)>,
+    // scatter water, rock, high ground and swamp on generated boards
+    pub terrain: bool,
+    // no edges. width and height are only where it starts
+    pub endless: bool,
+}
+
+impl GridConfig {
+    pub fn new(width: usize, height: usize, tile_size: f32) -> Self {
+        GridConfig {
+            width,
+            height,
+            tile_size,
+            seed: None,
+            topology: Topology::Square,
+            number_rule: NumberRule::Adjacent,
+            castle: None,
+            terrain: false,
+            endless: false,
+        }
+    }
+
+    // the castle sits here and territory grows out from it
+    pub fn center(&self) -> (usize, usize) {
+        self.castle.unwrap_or((self.width / 2, self.height / 2))
+    }
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        GridConfig::new(21, 21, 30.0)
+    }
+}
+
+// resource
+// all the mines are drawn from this
+// the same seed always gives the same board
+pub struct MineRng {
+    pub seed: u64,
+    rng: StdRng,
+}
+
+impl MineRng {
+    pub fn new(seed: u64) -> Self {
+        MineRng {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for MineRng {
+    fn default() -> Self {
+        MineRng::new(rand::thread_rng().gen())
+    }
+}
 
 // Events
 pub struct ClearSelectionsEvent;
 
+// mark or unmark a wall as a suspected mine
+pub struct FlagEvent {
+    pub x: i32,
+    pub y: i32,
+}
+
+// a tower went on a tile without a mine
+pub struct MisplacedTowerEvent {
+    pub x: i32,
+    pub y: i32,
+}
+
+// a number that can't be satisfied anymore
+// the ui says what's wrong with it
+pub struct NumberContradictionEvent {
+    pub x: i32,
+    pub y: i32,
+    pub contradiction: Contradiction,
+}
+
 #[derive(Default)]
 struct NumberTextures {
     handle: Handle<TextureAtlas>,
@@ -70,73 +191,82 @@ struct NumberTextures {
 #[derive(Component)]
 struct NumberSprite;
 
+// numbers past 9 don't have a sprite
+#[derive(Component)]
+struct NumberText;
+
+#[derive(Component)]
+struct FlagSprite;
+
 #[derive(Component)]
 pub struct Tile {
     colour: Color,
     selection: Color,
     hover: Color,
     floor: Color,
-    pub tile_state: TileState,
-    number: usize,
-    pub x: usize,
-    pub y: usize,
+    wrong: Color,
+    error: Color,
+    pub x: i32,
+    pub y: i32,
 }
 
 impl Tile {
-    fn new(colour: Color, floor: Color, x: usize, y: usize) -> Self {
+    fn new(colour: Color, floor: Color, x: i32, y: i32) -> Self {
         Tile {
             colour,
             selection: Color::MIDNIGHT_BLUE,
             hover: Color::ALICE_BLUE,
-            tile_state: TileState::Wall,
             floor,
-            number: 0,
+            // #a22633
+            wrong: Color::rgb_u8(0xa2, 0x26, 0x33),
+            // #e43b44
+            error: Color::rgb_u8(0xe4, 0x3b, 0x44),
             x,
             y,
         }
     }
 
-    pub fn try_spawn_tower(&mut self) -> Result<(), PlaceError> {
-        // if self.tile_state == TileState::Wall {
-        //     self.tile_state = TileState::Tower;
-        //     return Ok(());
-        // }
-        // Err(())
-        match self.tile_state {
-            TileState::Wall => {
-                self.tile_state = TileState::Tower;
-                Ok(())
-            }
-            TileState::Tower => Err(PlaceError::TowerAlready),
-            _ => Err(PlaceError::Floor),
-        }
+    pub fn coords(&self) -> Coords {
+        Coords::new(self.x, self.y)
     }
 
-    pub fn get_colour(&self) -> Color {
-        match self.tile_state {
-            TileState::Wall => self.colour,
-            TileState::Floor => self.floor,
-            TileState::Tower => self.floor,
+    // the state lives on the Board
+    // the tile only knows how to draw it
+    pub fn get_colour(&self, tile_state: Option<TileState>, terrain: Terrain) -> Color {
+        let colour = match tile_state {
+            Some(TileState::Wall) => self.colour,
+            // FlagSprite is drawn on top
+            Some(TileState::Flagged) => self.colour,
+            Some(TileState::Floor) => self.floor,
+            Some(TileState::Tower) => self.floor,
+            // #0099db
+            Some(TileState::Blocked) => Color::rgb_u8(0x00, 0x99, 0xdb),
             _ => Color::ANTIQUE_WHITE,
+        };
+        // terrain covers walls and shows through floor
+        match (tile_state, terrain_colour(terrain)) {
+            (Some(TileState::Wall | TileState::Flagged), Some(ground)) => ground,
+            (Some(TileState::Floor | TileState::Tower), Some(ground)) => Color::rgb(
+                (colour.r() + ground.r()) * 0.5,
+                (colour.g() + ground.g()) * 0.5,
+                (colour.b() + ground.b()) * 0.5,
+            ),
+            _ => colour,
         }
     }
 }
 
-#[derive(Debug)]
-pub enum PlaceError {
-    TowerAlready,
-    Floor,
-    //NotEdge,
-}
-
-#[allow(dead_code)]
-#[derive(PartialEq)]
-pub enum TileState {
-    Wall,
-    Floor,
-    Tower,
-    Number,
-    Castle,
+fn terrain_colour(terrain: Terrain) -> Option<Color> {
+    match terrain {
+        // water is always blocked, that has its own colour
+        Terrain::Plain | Terrain::Water => None,
+        // #5a6988
+        Terrain::Rock => Some(Color::rgb_u8(0x5a, 0x69, 0x88)),
+        // #c28569
+        Terrain::HighGround => Some(Color::rgb_u8(0xc2, 0x85, 0x69)),
+        // #733e39
+        Terrain::Swamp => Some(Color::rgb_u8(0x73, 0x3e, 0x39)),
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -145,156 +275,115 @@ pub struct TileInfo {
     //tile: Tile,
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-pub struct Coords {
-    pub x: i32,
-    pub y: i32,
+// tile entities, in the same chunks as the board
+// a chunk's tiles are spawned the first time the board has it
+pub struct Grid {
+    tiles: Chunks<Option<TileInfo>>,
+    width: usize,
+    height: usize,
+    endless: bool,
+    tile_size: f32,
+    topology: Topology,
 }
 
-impl Coords {
-    fn new(x: i32, y: i32) -> Self {
-        Coords { x, y }
+impl Grid {
+    fn new(config: &GridConfig, endless: bool) -> Self {
+        Grid {
+            tiles: Chunks::new(None),
+            width: config.width,
+            height: config.height,
+            endless,
+            tile_size: config.tile_size,
+            topology: config.topology,
+        }
     }
 
-    fn get_neighbour_coords(self) -> Vec<Coords> {
-        let a = Coords::new(self.x, self.y + 1);
-        let b = Coords::new(self.x + 1, self.y + 1);
-        let c = Coords::new(self.x + 1, self.y);
-        let d = Coords::new(self.x + 1, self.y - 1);
-        let e = Coords::new(self.x, self.y - 1);
-        let f = Coords::new(self.x - 1, self.y - 1);
-        let g = Coords::new(self.x - 1, self.y);
-        let h = Coords::new(self.x - 1, self.y + 1);
-
-        vec![a, b, c, d, e, f, g, h]
+    pub fn has_chunk(&self, chunk: Coords) -> bool {
+        self.tiles.has_chunk(chunk)
     }
 
-    fn get_ring_coords(self, radius: i32) -> Vec<Coords> {
-        let mut v = Vec::new();
-        for i in -radius..=radius {
-            for j in -radius..=radius {
-                if i == -radius || i == radius || j == -radius || j == radius {
-                    let x = self.x + i;
-                    let y = self.y + j;
-                    v.push(Coords::new(x, y));
-                }
-            }
+    fn insert(&mut self, coords: Coords, info: TileInfo) {
+        self.tiles.insert_chunk(chunk::chunk_of(coords));
+        if let Some(tile) = self.tiles.get_mut(coords) {
+            *tile = Some(info);
         }
-        v
     }
-}
 
-pub struct Grid {
-    pub tiles: Vec<TileInfo>,
-}
+    // Coordinates
+    // everything that turns a tile into a position or back goes through here
+    // the middle of the board is at the world origin
+
+    // world position of tile (0, 0)
+    // an endless board keeps the same origin as it grows
+    fn origin(&self) -> Vec2 {
+        Vec2::new(
+            -0.5 * (self.width - 1) as f32 * self.tile_size,
+            -0.5 * (self.height - 1) as f32 * self.tile_size,
+        )
+    }
 
-impl Grid {
-    pub fn get_vec2(&self, pos: Vec2) -> Option<TileInfo> {
-        let x = (((GRID_WIDTH - 1) as f32 * 0.5 * TILE_SIZE) + TILE_SIZE * 0.5 + pos.x) / TILE_SIZE;
-        let y = ((GRID_HEIGHT as f32 * 0.5 * TILE_SIZE) + TILE_SIZE * 0.5 + pos.y) / TILE_SIZE;
+    // the centre of a tile
+    pub fn tile_to_world(&self, coords: Coords) -> Vec2 {
+        // hex rows are offset like bricks
+        let shift = self.topology.row_shift(coords.y);
+        self.origin() + Vec2::new(coords.x as f32 + shift, coords.y as f32) * self.tile_size
+    }
 
-        if x < 0.0 || y < 0.0 {
-            return None;
-        }
+    pub fn world_to_tile(&self, pos: Vec2) -> Option<Coords> {
+        let local = (pos - self.origin()) / self.tile_size;
+        let y = (local.y + 0.5).floor() as i32;
+        // hex rows are drawn shifted, undo it once the row is known
+        let x = (local.x + 0.5 - self.topology.row_shift(y)).floor() as i32;
+        let coords = Coords::new(x, y);
+        self.contains(coords).then_some(coords)
+    }
 
-        self.get_xy(x as usize, y as usize)
+    // bottom left and top right corners
+    pub fn tile_bounds(&self, coords: Coords) -> (Vec2, Vec2) {
+        let center = self.tile_to_world(coords);
+        let half = Vec2::splat(self.tile_size * 0.5);
+        (center - half, center + half)
     }
 
-    pub fn get_xy(&self, x: usize, y: usize) -> Option<TileInfo> {
-        if x >= GRID_WIDTH || y >= GRID_HEIGHT {
-            return None;
-        }
+    pub fn contains(&self, coords: Coords) -> bool {
+        self.endless
+            || (coords.x >= 0
+                && coords.y >= 0
+                && (coords.x as usize) < self.width
+                && (coords.y as usize) < self.height)
+    }
 
-        // in order (0, 0), (0, 1), (0, 2)
-        let index = x * GRID_HEIGHT + y;
-        let tile = self.tiles.get(index);
-        tile.copied()
-        // tile.map(|&t| t)
-        // I guess this replaces this:
-        // match tile {
-        //     Some(&t) => Some(t),
-        //     None => None,
-        // }
-    }
-
-    fn get_coords(&self, coords: Coords) -> Option<TileInfo> {
-        self.get_xy(coords.x as usize, coords.y as usize)
-    }
-
-    fn get_neighbours(&self, x: usize, y: usize) -> Vec<Option<TileInfo>> {
-        let a = self.get_xy(x, y + 1);
-        let b = self.get_xy(x + 1, y + 1);
-        let c = self.get_xy(x + 1, y);
-        let mut d = None;
-        let mut e = None;
-        let mut f = None;
-        let mut g = None;
-        let mut h = None;
-        if y > 0 {
-            d = self.get_xy(x + 1, y - 1);
-            e = self.get_xy(x, y - 1);
-        }
-        if x > 0 && y > 0 {
-            f = self.get_xy(x - 1, y - 1);
-        }
-        if x > 0 {
-            g = self.get_xy(x - 1, y);
-            h = self.get_xy(x - 1, y + 1);
-        }
+    // where the cursor is in the world, as seen through this camera
+    // None when the cursor is outside the window
+    pub fn screen_to_world(
+        camera: &Camera,
+        camera_transform: &GlobalTransform,
+        window: &Window,
+    ) -> Option<Vec2> {
+        let screen_pos = window.cursor_position()?;
+        let window_size = Vec2::new(window.width() as f32, window.height() as f32);
 
-        vec![a, b, c, d, e, f, g, h]
-    }
-
-    #[allow(dead_code)]
-    fn get_5x5_ring(&self, x: usize, y: usize) -> Vec<Option<TileInfo>> {
-        // (x+2, y), (x-2, y)
-        // (x, y+2), (x, y-2)
-        // 16 tiles
-        // let a = self.get_xy(x-2, y+2);
-        let mut v = Vec::new();
-        for i in -2..=2 {
-            for j in -2..=2 {
-                // only do the edges
-                if i == -2 || i == 2 || j == -2 || j == 2 {
-                    let i2 = x as i32 + i;
-                    let j2 = y as i32 + j;
-
-                    let a = if i2 >= 0 && j2 >= 0 {
-                        self.get_xy(i2 as usize, j2 as usize)
-                    } else {
-                        None
-                    };
-
-                    v.push(a);
-                }
-            }
-        }
-        v
-    }
-
-    pub fn get_ring(&self, x: usize, y: usize, radius: i32) -> Vec<Option<TileInfo>> {
-        // radius of 0 is self
-        // 1 is 3x3
-        // 2 is 5x5, etc..
-        let mut v = Vec::new();
-        for i in -radius..=radius {
-            for j in -radius..=radius {
-                // only do the edges
-                if i == -radius || i == radius || j == -radius || j == radius {
-                    let i2 = x as i32 + i;
-                    let j2 = y as i32 + j;
-
-                    let a = if i2 >= 0 && j2 >= 0 {
-                        self.get_xy(i2 as usize, j2 as usize)
-                    } else {
-                        None
-                    };
-
-                    v.push(a);
-                }
-            }
-        }
-        v
+        // convert screen position [0..resolution] to ndc [-1..1] (gpu coords)
+        let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
+
+        // matrix for undoing the projection and camera transform
+        let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix().inverse();
+
+        // use it to convert ndc to world-space coordinates
+        let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
+
+        // reduce it to a 2D value
+        Some(world_pos.truncate())
+    }
+
+    pub fn get_vec2(&self, pos: Vec2) -> Option<TileInfo> {
+        let coords = self.world_to_tile(pos)?;
+        self.get(coords)
+    }
+
+    // None if it's off the board or its chunk hasn't been spawned
+    pub fn get(&self, coords: Coords) -> Option<TileInfo> {
+        self.tiles.get(coords).copied().flatten()
     }
 }
 
@@ -309,81 +398,397 @@ fn setup_atlas(
     numbers.handle = texture_atlas_handle;
 }
 
-fn setup_grid(mut commands: Commands, mut grid: ResMut<Grid>, numbers: Res<NumberTextures>) {
-    let offset = Vec3::new(
-        // 21 * 0.5 = 10.5 * 30.0
-        -0.5 * ((GRID_WIDTH - 1) as f32) * TILE_SIZE,
-        -0.5 * ((GRID_HEIGHT) as f32) * TILE_SIZE,
-        0.0,
+fn setup_grid(
+    mut commands: Commands,
+    mut grid: ResMut<Grid>,
+    mut board: ResMut<Board>,
+    mut mine_rng: ResMut<MineRng>,
+    config: Res<GridConfig>,
+    numbers: Res<NumberTextures>,
+    textures: Res<SpriteAssets>,
+    fonts: Res<FontAssets>,
+    loaded: Res<LoadedMap>,
+) {
+    if let Some(seed) = config.seed {
+        *mine_rng = MineRng::new(seed);
+    }
+    println!("Board seed: {}", mine_rng.seed);
+    build_grid(
+        &mut commands,
+        &mut grid,
+        &mut board,
+        &config,
+        loaded.0.as_ref(),
+        mine_rng.seed,
+        &numbers,
+        &textures,
+        &fonts,
     );
+}
 
-    for i in 0..GRID_WIDTH {
-        for j in 0..GRID_HEIGHT {
-            let even = (i + j) % 2 == 0;
-            let color = if even {
-                //Color::GREEN
-                // #b4dc25
-                //Color::rgb_u8(0xb4, 0xdc, 0x25)
-
-                // #3e8948
-                Color::rgb_u8(0x3e, 0x89, 0x48)
-            } else {
-                //Color::SEA_GREEN
-                // #26a630
-                //Color::rgb_u8(0x26, 0xa6, 0x30)
-                // #fbd439
-                //Color::rgb_u8(0xfb, 0xd4, 0x39)
-                // #fbffce
-                //Color::rgb_u8(0xfb, 0xff, 0xce)
-                // #265c42
-                //Color::rgb_u8(0x26, 0x5c, 0x42)
-                // #63c74d
-                // Color::rgb_u8(0x63, 0xc7, 0x4d)
-
-                // #265c42
-                Color::rgb_u8(0x26, 0x5c, 0x42)
-            };
+// T on the menu swaps between square and hex
+// the tiles are laid out differently so they're all made again
+fn cycle_topology(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    q_tiles: Query<Entity, With<Tile>>,
+    mut grid: ResMut<Grid>,
+    mut board: ResMut<Board>,
+    mut config: ResMut<GridConfig>,
+    numbers: Res<NumberTextures>,
+    textures: Res<SpriteAssets>,
+    fonts: Res<FontAssets>,
+    loaded: Res<LoadedMap>,
+    mine_rng: Res<MineRng>,
+) {
+    if !keyboard.just_pressed(KeyCode::T) {
+        return;
+    }
+    config.topology = config.topology.next();
+    println!("Topology: {:?}", config.topology);
+    for entity in q_tiles.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    build_grid(
+        &mut commands,
+        &mut grid,
+        &mut board,
+        &config,
+        loaded.0.as_ref(),
+        mine_rng.seed,
+        &numbers,
+        &textures,
+        &fonts,
+    );
+}
+
+// N on the menu picks what the numbers count
+// tiles look the same, only the board needs making again
+fn cycle_number_rule(
+    keyboard: Res<Input<KeyCode>>,
+    mut board: ResMut<Board>,
+    mut config: ResMut<GridConfig>,
+    loaded: Res<LoadedMap>,
+    mine_rng: Res<MineRng>,
+) {
+    if !keyboard.just_pressed(KeyCode::N) {
+        return;
+    }
+    config.number_rule = config.number_rule.next();
+    println!("Number rule: {:?}", config.number_rule);
+    *board = new_board(&config, loaded.0.as_ref(), mine_rng.seed);
+}
+
+// L on the menu turns terrain on generated boards on or off
+fn toggle_terrain(
+    keyboard: Res<Input<KeyCode>>,
+    mut q_tiles: Query<(&mut Sprite, &Tile)>,
+    mut board: ResMut<Board>,
+    mut config: ResMut<GridConfig>,
+    loaded: Res<LoadedMap>,
+    mine_rng: Res<MineRng>,
+) {
+    if !keyboard.just_pressed(KeyCode::L) {
+        return;
+    }
+    config.terrain = !config.terrain;
+    println!("Terrain: {}", config.terrain);
+    *board = new_board(&config, loaded.0.as_ref(), mine_rng.seed);
+    for (mut sprite, tile) in q_tiles.iter_mut() {
+        let c = tile.coords();
+        sprite.color = tile.get_colour(board.state(c), board.terrain(c));
+    }
+}
+
+// I on the menu makes generated boards endless
+// the board starts bigger than the screen, so the tiles are all made again
+fn toggle_endless(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    q_tiles: Query<Entity, With<Tile>>,
+    mut grid: ResMut<Grid>,
+    mut board: ResMut<Board>,
+    mut config: ResMut<GridConfig>,
+    numbers: Res<NumberTextures>,
+    textures: Res<SpriteAssets>,
+    fonts: Res<FontAssets>,
+    loaded: Res<LoadedMap>,
+    mine_rng: Res<MineRng>,
+) {
+    if !keyboard.just_pressed(KeyCode::I) {
+        return;
+    }
+    config.endless = !config.endless;
+    println!("Endless: {}", config.endless);
+    for entity in q_tiles.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    build_grid(
+        &mut commands,
+        &mut grid,
+        &mut board,
+        &config,
+        loaded.0.as_ref(),
+        mine_rng.seed,
+        &numbers,
+        &textures,
+        &fonts,
+    );
+}
 
-            // #e4a672
-            // #b86f50
-            // #e8b796
-            let floor = if even {
-                //Color::rgb_u8(0xb8, 0x6f, 0x50)
-                Color::rgb_u8(0xe4, 0xa6, 0x72)
-            } else {
-                //Color::rgb_u8(0xe4, 0xa6, 0x72)
-                Color::rgb_u8(0xe8, 0xb7, 0x96)
+// M on the menu swaps between a generated board and the map in assets/maps
+// the map picks the size, so the tiles are all made again
+fn toggle_map(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    q_tiles: Query<Entity, With<Tile>>,
+    mut grid: ResMut<Grid>,
+    mut board: ResMut<Board>,
+    mut config: ResMut<GridConfig>,
+    mut loaded: ResMut<LoadedMap>,
+    maps: Res<MapAssets>,
+    map_assets: Res<Assets<MapAsset>>,
+    numbers: Res<NumberTextures>,
+    textures: Res<SpriteAssets>,
+    fonts: Res<FontAssets>,
+    mine_rng: Res<MineRng>,
+    // the size to go back to
+    // None if the map didn't change it
+    mut generated_size: Local<Option<(usize, usize)>>,
+) {
+    if !keyboard.just_pressed(KeyCode::M) {
+        return;
+    }
+    match loaded.0.take() {
+        Some(_) => {
+            if let Some((width, height)) = generated_size.take() {
+                config.width = width;
+                config.height = height;
+            }
+            config.castle = None;
+            println!("Map: off");
+        }
+        None => {
+            // a map made in the editor wins over the shipped one
+            let map = match read_user_map() {
+                Some(map) => map,
+                None => match map_assets.get(&maps.level) {
+                    Some(asset) => asset.0.clone(),
+                    None => return,
+                },
             };
+            *generated_size = Some((config.width, config.height));
+            config.width = map.width;
+            config.height = map.height;
+            config.castle = Some((map.castle.x as usize, map.castle.y as usize));
+            println!("Map: {}x{}", map.width, map.height);
+            loaded.0 = Some(map);
+        }
+    }
+    for entity in q_tiles.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    build_grid(
+        &mut commands,
+        &mut grid,
+        &mut board,
+        &config,
+        loaded.0.as_ref(),
+        mine_rng.seed,
+        &numbers,
+        &textures,
+        &fonts,
+    );
+}
 
-            let pos = offset + Vec3::new(i as f32 * TILE_SIZE, j as f32 * TILE_SIZE, 0.0);
-
-            let tile = Tile::new(color, floor, i, j);
-            let tile_ent = commands
-                .spawn_bundle(SpriteBundle {
-                    sprite: Sprite {
-                        color,
-                        custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
-                        ..default()
-                    },
-                    transform: Transform::from_translation(pos),
+// terrain has its own rng from the seed
+// so it's the same however many times the board is made on the menu
+// maps always have edges
+fn new_board(config: &GridConfig, map: Option<&MapFile>, seed: u64) -> Board {
+    let (x, y) = config.center();
+    let center = Coords::new(x as i32, y as i32);
+    let mut board = if config.endless && map.is_none() {
+        Board::endless(center, config.topology, config.number_rule)
+    } else {
+        Board::new(
+            config.width,
+            config.height,
+            config.topology,
+            config.number_rule,
+        )
+    };
+    match map {
+        Some(map) => board.load_map(map),
+        None if config.terrain => {
+            board.scatter_terrain(center, &mut StdRng::seed_from_u64(seed));
+        }
+        None => {}
+    }
+    board.set_seed(seed);
+    board
+}
+
+fn build_grid(
+    commands: &mut Commands,
+    grid: &mut Grid,
+    board: &mut Board,
+    config: &GridConfig,
+    map: Option<&MapFile>,
+    seed: u64,
+    numbers: &NumberTextures,
+    textures: &SpriteAssets,
+    fonts: &FontAssets,
+) {
+    *board = new_board(config, map, seed);
+    *grid = Grid::new(config, board.is_endless());
+    let chunks: Vec<Coords> = board.chunks().collect();
+    for c in chunks {
+        spawn_chunk(
+            commands,
+            grid,
+            board,
+            c,
+            config.tile_size,
+            numbers,
+            textures,
+            fonts,
+        );
+    }
+}
+
+// an endless board grows as the territory reaches new chunks
+fn spawn_new_chunks(
+    mut commands: Commands,
+    mut grid: ResMut<Grid>,
+    board: Res<Board>,
+    config: Res<GridConfig>,
+    numbers: Res<NumberTextures>,
+    textures: Res<SpriteAssets>,
+    fonts: Res<FontAssets>,
+) {
+    if !board.is_changed() {
+        return;
+    }
+    let chunks: Vec<Coords> = board.chunks().filter(|&c| !grid.has_chunk(c)).collect();
+    for c in chunks {
+        println!("New chunk at {}, {}", c.x, c.y);
+        spawn_chunk(
+            &mut commands,
+            &mut grid,
+            &board,
+            c,
+            config.tile_size,
+            &numbers,
+            &textures,
+            &fonts,
+        );
+    }
+}
+
+fn spawn_chunk(
+    commands: &mut Commands,
+    grid: &mut Grid,
+    board: &Board,
+    chunk: Coords,
+    tile_size: f32,
+    numbers: &NumberTextures,
+    textures: &SpriteAssets,
+    fonts: &FontAssets,
+) {
+    // the chunk is marked even if the board's edge leaves it empty
+    grid.tiles.insert_chunk(chunk);
+    for c in chunk::chunk_tiles(chunk) {
+        if board.get(c).is_none() {
+            continue;
+        }
+        let even = (c.x + c.y).rem_euclid(2) == 0;
+        let color = if even {
+            //Color::GREEN
+            // #b4dc25
+            //Color::rgb_u8(0xb4, 0xdc, 0x25)
+
+            // #3e8948
+            Color::rgb_u8(0x3e, 0x89, 0x48)
+        } else {
+            //Color::SEA_GREEN
+            // #26a630
+            //Color::rgb_u8(0x26, 0xa6, 0x30)
+            // #fbd439
+            //Color::rgb_u8(0xfb, 0xd4, 0x39)
+            // #fbffce
+            //Color::rgb_u8(0xfb, 0xff, 0xce)
+            // #265c42
+            //Color::rgb_u8(0x26, 0x5c, 0x42)
+            // #63c74d
+            // Color::rgb_u8(0x63, 0xc7, 0x4d)
+
+            // #265c42
+            Color::rgb_u8(0x26, 0x5c, 0x42)
+        };
+
+        // #e4a672
+        // #b86f50
+        // #e8b796
+        let floor = if even {
+            //Color::rgb_u8(0xb8, 0x6f, 0x50)
+            Color::rgb_u8(0xe4, 0xa6, 0x72)
+        } else {
+            //Color::rgb_u8(0xe4, 0xa6, 0x72)
+            Color::rgb_u8(0xe8, 0xb7, 0x96)
+        };
+
+        let tile = Tile::new(color, floor, c.x, c.y);
+        let pos = grid.tile_to_world(c).extend(0.0);
+        // so the menu shows maps and terrain before the game starts
+        let color = tile.get_colour(board.state(c), board.terrain(c));
+        let tile_ent = commands
+            .spawn_bundle(SpriteBundle {
+                sprite: Sprite {
+                    color,
+                    custom_size: Some(Vec2::new(tile_size, tile_size)),
+                    ..default()
+                },
+                transform: Transform::from_translation(pos),
+                ..default()
+            })
+            .insert(tile)
+            .insert(Interaction::None)
+            .with_children(|tile| {
+                tile.spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: numbers.handle.clone(),
                     ..default()
                 })
-                .insert(tile)
-                .insert(Interaction::None)
-                .with_children(|tile| {
-                    tile.spawn_bundle(SpriteSheetBundle {
-                        texture_atlas: numbers.handle.clone(),
-                        ..default()
-                    })
-                    .insert(NumberSprite);
+                .insert(NumberSprite);
+
+                // hidden until the number is too big for the sprite
+                tile.spawn_bundle(Text2dBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: fonts.fira_sans.clone(),
+                            font_size: tile_size * 0.6,
+                            color: Color::BLACK,
+                        },
+                    )
+                    .with_alignment(TextAlignment::CENTER),
+                    transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                    visibility: Visibility { is_visible: false },
+                    ..default()
                 })
-                .id();
+                .insert(NumberText);
 
-            grid.tiles.push(TileInfo {
-                entity: tile_ent,
-                //tile: tile,
-            });
-        }
+                // hidden until the tile is flagged
+                tile.spawn_bundle(SpriteBundle {
+                    texture: textures.flag.clone(),
+                    transform: Transform::from_xyz(0.0, 0.0, 0.1),
+                    visibility: Visibility { is_visible: false },
+                    ..default()
+                })
+                .insert(FlagSprite);
+            })
+            .id();
+
+        grid.insert(c, TileInfo { entity: tile_ent });
     }
 }
 
@@ -408,18 +813,23 @@ pub fn clear_interaction(mut q_tile: Query<(&mut Interaction, Option<&Selection>
 
 // with<tile> stops it from messing with UI stuff I might have
 pub fn interaction(
-    mut q_tile: Query<&mut Interaction, With<Tile>>,
+    mut q_tile: Query<(&mut Interaction, &Tile)>,
     grid: Res<Grid>,
     mouse: Res<MouseWorldPos>,
     mouse_click: Res<Input<MouseButton>>,
+    keyboard: Res<Input<KeyCode>>,
     mut ev_clear: EventWriter<ClearSelectionsEvent>,
+    mut ev_flag: EventWriter<FlagEvent>,
+    mut ev_chord: EventWriter<ChordEvent>,
+    board: Res<Board>,
 ) {
     let hovered = grid.get_vec2(mouse.0);
     let left_clicked = mouse_click.just_pressed(MouseButton::Left);
     let right_clicked = mouse_click.just_pressed(MouseButton::Right);
+    let flag_pressed = right_clicked || keyboard.just_pressed(KeyCode::F);
     if let Some(info) = hovered {
         let target = q_tile.get_mut(info.entity);
-        if let Ok(mut interaction) = target {
+        if let Ok((mut interaction, tile)) = target {
             match *interaction {
                 Interaction::Clicked => {
                     // do nothing
@@ -438,6 +848,18 @@ pub fn interaction(
                     }
                 }
             }
+            if flag_pressed {
+                ev_flag.send(FlagEvent {
+                    x: tile.x,
+                    y: tile.y,
+                });
+            }
+            if left_clicked && board.state(tile.coords()) == Some(TileState::Floor) {
+                ev_chord.send(ChordEvent {
+                    x: tile.x,
+                    y: tile.y,
+                });
+            }
         }
     } else if left_clicked {
         // sometimes won't spawn a tower
@@ -463,9 +885,10 @@ pub fn interaction(
 #[derive(Component)]
 pub struct Selection;
 
-fn tile_interaction(
+pub fn tile_interaction(
     mut commands: Commands,
     mut q_interaction: Query<(Entity, &Interaction, &mut Sprite, &Tile)>,
+    board: Res<Board>,
 ) {
     for (entity, interaction, mut sprite, tile) in q_interaction.iter_mut() {
         match *interaction {
@@ -477,7 +900,14 @@ fn tile_interaction(
                 sprite.color = tile.hover;
             }
             Interaction::None => {
-                sprite.color = tile.get_colour();
+                sprite.color = if board.is_misplaced(tile.coords()) {
+                    tile.wrong
+                } else if board.contradiction(tile.coords()).is_some() {
+                    tile.error
+                } else {
+                    let c = tile.coords();
+                    tile.get_colour(board.state(c), board.terrain(c))
+                };
             }
         }
     }
@@ -499,199 +929,59 @@ pub fn clear_selection(
 }
 
 fn expand_floor(
-    mut q_tiles: Query<(&mut Tile, &mut Sprite)>,
-    grid: Res<Grid>,
+    mut board: ResMut<Board>,
+    mut mine_rng: ResMut<MineRng>,
     territory_info: Res<TerritoryInfo>,
     ev_expand: EventReader<ExpandAreaEvent>,
 ) {
     if !ev_expand.is_empty() {
         ev_expand.clear();
-        let x = territory_info.x;
-        let y = territory_info.y;
-        let radius = territory_info.radius;
-        let center_coords = Coords::new(x as i32, y as i32);
-
-        if radius == 1 {
-            // set center to floor
-            let center = grid.get_coords(center_coords);
-            if let Some(info) = center {
-                if let Ok((mut tile, mut sprite)) = q_tiles.get_mut(info.entity) {
-                    tile.tile_state = TileState::Floor;
-                    sprite.color = tile.get_colour();
-                    tile.number = 0;
-                }
-            }
-        }
-        // radius goes up += 2
-        if radius > 2 && !territory_info.battlements_style {
-            // Brute force
-            // for (mut tile, mut _sprite) in q_tiles.iter_mut() {
-            //     if tile.tile_state == TileState::Floor {
-            //         tile.number = 0;
-            //     }
-            // }
-            // clear current numbers
-            let neighbours = grid.get_ring(x, y, radius - 2);
-            for info in neighbours.iter().flatten() {
-                // if let Some(info) = ent {
-                if let Ok((mut tile, mut _sprite)) = q_tiles.get_mut(info.entity) {
-                    if tile.tile_state == TileState::Floor {
-                        tile.number = 0;
-                    }
-                }
-                // }
-            }
-            // clear old walls
-            let neighbours = grid.get_ring(x, y, radius - 1);
-            for info in neighbours.iter().flatten() {
-                // if let Some(info) = ent {
-                if let Ok((mut tile, mut sprite)) = q_tiles.get_mut(info.entity) {
-                    if tile.tile_state == TileState::Wall {
-                        tile.tile_state = TileState::Floor;
-                        sprite.color = tile.get_colour();
-                    }
-                }
-                // }
-            }
+        // tile_interaction picks up the new colours
+        if !board.expand(&territory_info, &mut mine_rng.rng) {
+            println!("No guess ring took mines out so every number can be filled");
         }
-        // battlements
-        if territory_info.battlements_style {
-            // radius starts at 1 for the 3x3
-            let mut wall_set: HashSet<Coords> = HashSet::new();
-            let mut floor_set: HashSet<Coords> = HashSet::new();
-            let inner = center_coords.get_ring_coords(radius);
-            let outer = center_coords.get_ring_coords(radius + 1);
-            if radius > 2 {
-                // clear old
-                let mut old_inner = center_coords.get_ring_coords(radius - 2);
-                let mut old_outer = center_coords.get_ring_coords(radius - 1);
-
-                // only inner will have a number, but not worth separating for that
-                old_inner.append(&mut old_outer);
-
-                // skip towers
-                // set walls to floor
-                // number to 0
-                // color adjustment
-                for c in old_inner {
-                    if let Some(info) = grid.get_coords(c) {
-                        if let Ok((mut tile, mut sprite)) = q_tiles.get_mut(info.entity) {
-                            if tile.tile_state != TileState::Tower {
-                                tile.number = 0;
-                                tile.tile_state = TileState::Floor;
-                                sprite.color = tile.get_colour();
-                            }
-                        }
-                    }
-                }
-            }
-
-            for i in inner {
-                if (i.x + i.y) % 2 == 0 {
-                    floor_set.insert(i);
-                } else {
-                    wall_set.insert(i);
-                }
-            }
-            for o in outer {
-                wall_set.insert(o);
-            }
-
-            let mut random_set = HashSet::new();
-            let number_total = wall_set.len() as f32 * territory_info.bombs_percent;
-
-            for (i, c) in wall_set.drain().enumerate() {
-                if i > number_total.floor() as usize {
-                    break;
-                }
-                random_set.insert(c);
-            }
+    }
+}
 
-            // calculate numbers
-            for floor in floor_set {
-                let mut number = 0;
-                let n_coords = floor.get_ring_coords(1);
-                for c in n_coords {
-                    // is it a bomb?
-                    if random_set.contains(&c) {
-                        number += 1;
-                    }
-                }
-                // set the number
-                // and set to floor
-                // and change colour
-                if let Some(info) = grid.get_coords(floor) {
-                    if let Ok((mut tile, mut sprite)) = q_tiles.get_mut(info.entity) {
-                        tile.number = number;
-                        tile.tile_state = TileState::Floor;
-                        sprite.color = tile.get_colour();
-                    }
-                }
-            }
-        } else {
-            // set all to floor
-            let neighbours = grid.get_ring(x, y, radius);
-            for info in neighbours.iter().flatten() {
-                // if let Some(info) = ent {
-                if let Ok((mut tile, mut sprite)) = q_tiles.get_mut(info.entity) {
-                    if tile.tile_state != TileState::Tower {
-                        tile.tile_state = TileState::Floor;
-                        sprite.color = tile.get_colour();
-                    }
-                }
-                // }
-            }
+fn chord(
+    mut ev_chord: EventReader<ChordEvent>,
+    mut board: ResMut<Board>,
+    territory_info: Res<TerritoryInfo>,
+    mut ev_contradiction: EventWriter<NumberContradictionEvent>,
+) {
+    for ev in ev_chord.iter() {
+        let number = Coords::new(ev.x, ev.y);
+        let cleared = match territory_info.expansion {
+            // flood fill is a chord that keeps going
+            ExpansionRule::FloodFill => board.flood(number, territory_info.bombs_percent),
+            _ => board.chord(number, territory_info.bombs_percent),
+        };
+        if cleared.is_empty() {
+            continue;
+        }
+        println!("Chord at {}, {} cleared {}", ev.x, ev.y, cleared.len());
+        // numbers next to the cleared tiles have fewer walls now
+        // and the cleared tiles have numbers of their own
+        let mut changed = cleared.clone();
+        for c in cleared {
+            changed.append(&mut board.number_area(c));
+        }
+        send_contradictions(&board, changed, &mut ev_contradiction);
+    }
+}
 
-            let mut wall_set: HashSet<Coords> = HashSet::new();
-            // get a set of all the walls in neighbours of the neighbours
-            let neighbours = center_coords.get_ring_coords(radius);
-            for &c in neighbours.iter() {
-                if c.x > 0 && c.y > 0 {
-                    if let Some(_info) = grid.get_coords(c) {
-                        let wall_coords = c.get_neighbour_coords();
-                        for &wc in wall_coords.iter() {
-                            if let Some(info2) = grid.get_coords(wc) {
-                                if let Ok((tile, _sprite)) = q_tiles.get(info2.entity) {
-                                    if tile.tile_state == TileState::Wall {
-                                        wall_set.insert(Coords::new(tile.x as i32, tile.y as i32));
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            // could this just be the 5x5? instead of neighbours of neighbours
-
-            let mut random_set = HashSet::new();
-            let number_total = wall_set.len() as f32 * territory_info.bombs_percent;
-            // pick random coords to have bombs
-            //wall_set.remove(value)
-            for (i, c) in wall_set.drain().enumerate() {
-                if i >= number_total.floor() as usize {
-                    break;
-                }
-                random_set.insert(c);
-                println!("Bomb at {:?}", c);
-            }
-            // coordinates of the bombs
-            // for each bomb, check its neighbours
-            // if the neighbour is a floor, increment its count
-            for c in random_set.drain() {
-                let n = c.get_neighbour_coords();
-                for c in n {
-                    if let Some(info) = grid.get_coords(c) {
-                        if let Ok((mut tile, _sprite)) = q_tiles.get_mut(info.entity) {
-                            if tile.tile_state == TileState::Floor {
-                                let ct = Coords::new(tile.x as i32, tile.y as i32);
-                                if ct == c {
-                                    tile.number += 1;
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+fn send_contradictions(
+    board: &Board,
+    numbers: Vec<Coords>,
+    ev_contradiction: &mut EventWriter<NumberContradictionEvent>,
+) {
+    for c in numbers {
+        if let Some(contradiction) = board.contradiction(c) {
+            ev_contradiction.send(NumberContradictionEvent {
+                x: c.x,
+                y: c.y,
+                contradiction,
+            });
         }
     }
 }
@@ -702,41 +992,73 @@ fn update_numbers(
         (&mut TextureAtlasSprite, &Handle<TextureAtlas>, &Parent),
         With<NumberSprite>,
     >,
+    mut q_tile_text: Query<(&mut Text, &mut Visibility, &Parent), With<NumberText>>,
+    board: Res<Board>,
 ) {
+    // frame 0 is blank, 1-9 are the digits
     for (mut sprite, _handle, parent) in q_tile_numbers.iter_mut() {
         let tile = q_tiles.get(parent.get()).unwrap();
-        sprite.index = tile.number % 9;
+        let remaining = board.remaining(tile.coords());
+        sprite.index = if remaining > 9 { 0 } else { remaining };
+    }
+    for (mut text, mut vis, parent) in q_tile_text.iter_mut() {
+        let tile = q_tiles.get(parent.get()).unwrap();
+        let remaining = board.remaining(tile.coords());
+        vis.is_visible = remaining > 9;
+        if vis.is_visible {
+            text.sections[0].value = remaining.to_string();
+        }
+    }
+}
+
+fn toggle_flags(mut ev_flag: EventReader<FlagEvent>, mut board: ResMut<Board>) {
+    for ev in ev_flag.iter() {
+        board.toggle_flag(Coords::new(ev.x, ev.y));
+    }
+}
+
+fn update_flags(
+    q_tiles: Query<&Tile>,
+    mut q_flags: Query<(&mut Visibility, &Parent), With<FlagSprite>>,
+    board: Res<Board>,
+) {
+    for (mut vis, parent) in q_flags.iter_mut() {
+        if let Ok(tile) = q_tiles.get(parent.get()) {
+            vis.is_visible = board.state(tile.coords()) == Some(TileState::Flagged);
+        }
     }
 }
 
-fn decrement_numbers(
+fn count_towers(
     mut ev_tower_spawned: EventReader<TowerPlacedEvent>,
-    mut q_tiles: Query<&mut Tile>,
-    grid: Res<Grid>,
+    mut board: ResMut<Board>,
     mut ev_number_filled: EventWriter<NumberFilledEvent>,
+    mut ev_misplaced: EventWriter<MisplacedTowerEvent>,
+    mut ev_contradiction: EventWriter<NumberContradictionEvent>,
 ) {
     for ev in ev_tower_spawned.iter() {
+        let tower = Coords::new(ev.x, ev.y);
+        if board.is_misplaced(tower) {
+            println!("No mine under tower: {}, {}", ev.x, ev.y);
+            ev_misplaced.send(MisplacedTowerEvent { x: ev.x, y: ev.y });
+        }
+
         // check for number around the tower
-        let neighbours = grid.get_neighbours(ev.x, ev.y);
-        for n in neighbours.iter().flatten() {
-            // if let Some(n) = n {
-            if let Ok(mut tile) = q_tiles.get_mut(n.entity) {
-                if tile.tile_state == TileState::Floor && tile.number > 0 {
-                    tile.number -= 1;
-
-                    // only count as filled if the tower being placed
-                    // caused this to go to 0
-                    // if it started at 0, it's fine
-                    if tile.number == 0 {
-                        println!(
-                            "Number filled at tower: {}, {} Tile: {}, {}",
-                            ev.x, ev.y, tile.x, tile.y
-                        );
-                        ev_number_filled.send(NumberFilledEvent);
-                    }
-                }
-            }
-            // }
+        // a misplaced tower still counts towards them
+        // but only towers on mines can fill them
+        for filled in board.count_tower(tower) {
+            println!(
+                "Number filled at tower: {}, {} Tile: {}, {}",
+                ev.x, ev.y, filled.x, filled.y
+            );
+            ev_number_filled.send(NumberFilledEvent {
+                x: filled.x,
+                y: filled.y,
+            });
         }
+
+        // one less wall and one more tower around these
+        let around = board.number_area(tower);
+        send_contradictions(&board, around, &mut ev_contradiction);
     }
 }
@@ -0,0 +1,112 @@
+use bevy::prelude::*;
+
+use crate::{
+    board::Board,
+    castle::Castle,
+    grid::{tile_interaction, Grid, Tile},
+    solver::{self, Deduction},
+    GameState,
+};
+
+pub struct HintPlugin;
+
+impl Plugin for HintPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(request_hint)
+                .with_system(show_hints.after(tile_interaction)),
+        );
+    }
+}
+
+const HINT_COST: u32 = 10;
+// how long the tile stays lit up
+const HINT_SECONDS: f32 = 5.0;
+
+// Components
+// on the tile the solver picked
+#[derive(Component)]
+pub struct Hint {
+    deduction: Deduction,
+    timer: Timer,
+}
+
+impl Hint {
+    fn colour(&self) -> Color {
+        match self.deduction {
+            // #feae34
+            Deduction::Mine => Color::rgb_u8(0xfe, 0xae, 0x34),
+            // #2ce8f5
+            Deduction::Safe => Color::rgb_u8(0x2c, 0xe8, 0xf5),
+        }
+    }
+}
+
+fn request_hint(
+    mut commands: Commands,
+    keyboard: Res<Input<KeyCode>>,
+    board: Res<Board>,
+    grid: Res<Grid>,
+    q_hints: Query<&Tile, With<Hint>>,
+    mut q_castle: Query<&mut Castle>,
+) {
+    if !keyboard.just_pressed(KeyCode::H) {
+        return;
+    }
+
+    for mut castle in q_castle.iter_mut() {
+        if HINT_COST > castle.money {
+            println!("Hint failed. Too expensive");
+            continue;
+        }
+
+        // don't pay twice for a tile that's already lit up
+        let shown: Vec<_> = q_hints.iter().map(|tile| tile.coords()).collect();
+        let mut deductions: Vec<_> = solver::deduce(&board)
+            .into_iter()
+            .filter(|(coords, _)| !shown.contains(coords))
+            .collect();
+        // a mine is more use than a safe tile. It's somewhere to build
+        deductions.sort_by_key(|(_, deduction)| *deduction != Deduction::Mine);
+
+        match deductions.first() {
+            Some(&(coords, deduction)) => {
+                if let Some(info) = grid.get(coords) {
+                    castle.money -= HINT_COST;
+                    println!("Hint: {}, {} is {:?}", coords.x, coords.y, deduction);
+                    commands.entity(info.entity).insert(Hint {
+                        deduction,
+                        timer: Timer::from_seconds(HINT_SECONDS, false),
+                    });
+                }
+            }
+            None => {
+                // could be a guess, or the numbers don't add up anymore
+                println!("No hint. Nothing is certain from the numbers");
+            }
+        }
+    }
+}
+
+// runs after tile_interaction so it draws over the normal colour
+// hovering and selecting still show as normal
+fn show_hints(
+    mut commands: Commands,
+    mut q_hints: Query<(Entity, &Interaction, &mut Sprite, &Tile, &mut Hint)>,
+    board: Res<Board>,
+    time: Res<Time>,
+) {
+    for (entity, interaction, mut sprite, tile, mut hint) in q_hints.iter_mut() {
+        hint.timer.tick(time.delta());
+        let still_wall = matches!(board.state(tile.coords()), Some(s) if s.is_wall());
+        if hint.timer.finished() || !still_wall {
+            commands.entity(entity).remove::<Hint>();
+            continue;
+        }
+
+        if *interaction == Interaction::None {
+            sprite.color = hint.colour();
+        }
+    }
+}
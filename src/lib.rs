@@ -2,17 +2,28 @@
 
 use bevy::{prelude::*, render::camera::RenderTarget};
 
+pub mod board;
+mod camera;
 mod castle;
+mod chunk;
 mod director;
+mod editor;
 mod enemy;
 mod flow_field;
+mod fog;
+pub mod graph;
 mod grid;
+mod hint;
 mod loading;
-mod pathfinding;
+mod map;
+pub mod pathfinding;
+mod solver;
 mod tower;
 mod ui;
 mod utility;
 
+pub use grid::GridConfig;
+
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
@@ -20,13 +31,16 @@ impl Plugin for GamePlugin {
         app.add_state(GameState::Loading)
             .insert_resource(MouseWorldPos(Vec2::ONE * 10000.0))
             .add_plugin(loading::LoadingPlugin)
+            .add_plugin(camera::CameraPlugin)
             .add_plugin(grid::GridPlugin)
             .add_plugin(ui::UiPlugin)
             .add_plugin(tower::TowerPlugin)
             .add_plugin(castle::CastlePlugin)
+            .add_plugin(hint::HintPlugin)
+            .add_plugin(editor::EditorPlugin)
+            .add_plugin(fog::FogPlugin)
             .add_plugin(director::DirectorPlugin)
             .add_plugin(enemy::EnemyPlugin)
-            .add_plugin(pathfinding::PathfindingPlugin)
             .add_plugin(flow_field::FlowFieldPlugin)
             .add_system(update_mouse_position);
     }
@@ -38,41 +52,30 @@ enum GameState {
     MainMenu,
     // Tutorial,
     Playing,
+    // making maps
+    Editor,
     End,
 }
 
 pub struct MouseWorldPos(Vec2);
 
+// only looks through the GameCamera, so other cameras can't move the mouse
 fn update_mouse_position(
     windows: Res<Windows>,
-    q_camera: Query<(&Camera, &GlobalTransform)>,
+    q_camera: Query<(&Camera, &GlobalTransform), With<camera::GameCamera>>,
     mut mouse_pos: ResMut<MouseWorldPos>,
 ) {
-    //let (camera, camera_transform) = q_camera.single();
-    for (camera, camera_transform) in q_camera.iter() {
-        let win = if let RenderTarget::Window(id) = camera.target {
-            windows.get(id).unwrap()
-        } else {
-            windows.get_primary().unwrap()
-        };
-
-        if let Some(screen_pos) = win.cursor_position() {
-            let window_size = Vec2::new(win.width() as f32, win.height() as f32);
-
-            // convert screen position [0..resolution] to ndc [-1..1] (gpu coords)
-            let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
-
-            // matrix for undoing the projection and camera transform
-            let ndc_to_world =
-                camera_transform.compute_matrix() * camera.projection_matrix().inverse();
-
-            // use it to convert ndc to world-space coordinates
-            let world_pos = ndc_to_world.project_point3(ndc.extend(-1.0));
-
-            // reduce it to a 2D value
-            let world_pos: Vec2 = world_pos.truncate();
+    let (camera, camera_transform) = match q_camera.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let win = if let RenderTarget::Window(id) = camera.target {
+        windows.get(id).unwrap()
+    } else {
+        windows.get_primary().unwrap()
+    };
 
-            mouse_pos.0 = world_pos;
-        }
+    if let Some(world_pos) = grid::Grid::screen_to_world(camera, camera_transform, win) {
+        mouse_pos.0 = world_pos;
     }
 }
@@ -1,5 +1,10 @@
-use crate::GameState;
-use bevy::prelude::*;
+use crate::{map::MapFile, GameState};
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
 use bevy_asset_loader::prelude::*;
 
 pub struct LoadingPlugin;
@@ -9,12 +14,16 @@ pub struct LoadingPlugin;
 /// If interested, take a look at https://bevy-cheatbook.github.io/features/assets.html
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_loading_state(
-            LoadingState::new(GameState::Loading)
-                .with_collection::<FontAssets>()
-                .with_collection::<SpriteAssets>()
-                .continue_to_state(GameState::MainMenu),
-        );
+        // maps are a custom asset, so the loader has to exist before loading starts
+        app.add_asset::<MapAsset>()
+            .init_asset_loader::<MapLoader>()
+            .add_loading_state(
+                LoadingState::new(GameState::Loading)
+                    .with_collection::<FontAssets>()
+                    .with_collection::<SpriteAssets>()
+                    .with_collection::<MapAssets>()
+                    .continue_to_state(GameState::MainMenu),
+            );
     }
 }
 
@@ -37,6 +46,9 @@ pub struct SpriteAssets {
     #[asset(path = "sprites/duck.png")]
     pub duck: Handle<Image>,
 
+    #[asset(path = "sprites/flag.png")]
+    pub flag: Handle<Image>,
+
     #[asset(path = "sprites/magic.png")]
     pub magic: Handle<Image>,
 
@@ -49,3 +61,37 @@ pub struct SpriteAssets {
     #[asset(path = "sprites/title.png")]
     pub title: Handle<Image>,
 }
+
+#[derive(AssetCollection)]
+pub struct MapAssets {
+    #[asset(path = "maps/level1.map")]
+    pub level: Handle<MapAsset>,
+}
+
+// a map file, as an asset
+// see map.rs for the format
+#[derive(TypeUuid)]
+#[uuid = "3b6c2f0e-8f4d-4a51-9c57-2d1e0b7a6f93"]
+pub struct MapAsset(pub MapFile);
+
+#[derive(Default)]
+struct MapLoader;
+
+impl AssetLoader for MapLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let text = std::str::from_utf8(bytes)?;
+            let map = MapFile::parse(text)?;
+            load_context.set_default_asset(LoadedAsset::new(MapAsset(map)));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["map"]
+    }
+}
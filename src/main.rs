@@ -7,7 +7,7 @@
 use bevy::prelude::*;
 use bevy_prototype_lyon::prelude::*;
 use bevy_rapier2d::prelude::*;
-use minesweeper_td::GamePlugin;
+use minesweeper_td::{GamePlugin, GridConfig};
 
 fn main() {
     App::new()
@@ -23,6 +23,10 @@ fn main() {
             title: "Minesweeper TD".to_string(),
             ..default()
         })
+        .insert_resource(GridConfig {
+            seed: seed_arg(),
+            ..default()
+        })
         .add_plugins(DefaultPlugins)
         .add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(100.0))
         //.add_plugin(RapierDebugRenderPlugin::default())
@@ -32,10 +36,26 @@ fn main() {
             gravity: Vec2::ZERO,
             ..default()
         })
-        .add_startup_system(setup)
         .run();
 }
 
-fn setup(mut commands: Commands) {
-    commands.spawn_bundle(Camera2dBundle::default());
+// replay a board with --seed 1234 or MINESWEEPER_SEED=1234
+fn seed_arg() -> Option<u64> {
+    let mut args = std::env::args().skip(1);
+    let mut text = None;
+    while let Some(arg) = args.next() {
+        if arg == "--seed" {
+            text = args.next();
+        } else if let Some(value) = arg.strip_prefix("--seed=") {
+            text = Some(value.to_string());
+        }
+    }
+    let text = text.or_else(|| std::env::var("MINESWEEPER_SEED").ok())?;
+    match text.trim().parse() {
+        Ok(seed) => Some(seed),
+        Err(_) => {
+            println!("Seed has to be a number, not {}", text);
+            None
+        }
+    }
 }
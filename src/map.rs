@@ -0,0 +1,354 @@
+use std::fmt;
+
+use crate::board::{Coords, Terrain};
+
+// Hand-made boards, written as text. One character per tile, top row first.
+//   #  wall. generation decides if it has a mine
+//   *  wall with a mine
+//   -  wall that never has a mine
+//   .  floor, already revealed
+//   X  water. Nothing goes here
+//   ^  rock wall
+//   H  high ground wall
+//   %  swamp wall
+//   C  the castle. Exactly one
+//   S  enemy spawn point. A wall as far as the board cares
+// Lines starting with // are comments. Blank lines are skipped.
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MapTile {
+    Wall,
+    Mine,
+    Empty,
+    Floor,
+    Water,
+    Rock,
+    HighGround,
+    Swamp,
+    Castle,
+    Spawn,
+}
+
+impl MapTile {
+    pub fn symbol(self) -> char {
+        match self {
+            MapTile::Wall => '#',
+            MapTile::Mine => '*',
+            MapTile::Empty => '-',
+            MapTile::Floor => '.',
+            MapTile::Water => 'X',
+            MapTile::Rock => '^',
+            MapTile::HighGround => 'H',
+            MapTile::Swamp => '%',
+            MapTile::Castle => 'C',
+            MapTile::Spawn => 'S',
+        }
+    }
+
+    pub fn from_symbol(symbol: char) -> Option<MapTile> {
+        match symbol {
+            '#' => Some(MapTile::Wall),
+            '*' => Some(MapTile::Mine),
+            '-' => Some(MapTile::Empty),
+            '.' => Some(MapTile::Floor),
+            'X' => Some(MapTile::Water),
+            '^' => Some(MapTile::Rock),
+            'H' => Some(MapTile::HighGround),
+            '%' => Some(MapTile::Swamp),
+            'C' => Some(MapTile::Castle),
+            'S' => Some(MapTile::Spawn),
+            _ => None,
+        }
+    }
+
+    pub fn terrain(self) -> Terrain {
+        match self {
+            MapTile::Water => Terrain::Water,
+            MapTile::Rock => Terrain::Rock,
+            MapTile::HighGround => Terrain::HighGround,
+            MapTile::Swamp => Terrain::Swamp,
+            _ => Terrain::Plain,
+        }
+    }
+
+    fn from_terrain(terrain: Terrain) -> MapTile {
+        match terrain {
+            Terrain::Water => MapTile::Water,
+            Terrain::Rock => MapTile::Rock,
+            Terrain::HighGround => MapTile::HighGround,
+            Terrain::Swamp => MapTile::Swamp,
+            Terrain::Plain => MapTile::Wall,
+        }
+    }
+}
+
+// the editor saves here so the shipped level never gets written over
+pub const USER_MAP_PATH: &str = "assets/maps/user.map";
+
+// the map saved from the editor, if there is one
+// a broken file is skipped so the shipped level still loads
+pub fn read_user_map() -> Option<MapFile> {
+    let text = std::fs::read_to_string(USER_MAP_PATH).ok()?;
+    match MapFile::parse(&text) {
+        Ok(map) => Some(map),
+        Err(e) => {
+            println!("Couldn't load {}: {}", USER_MAP_PATH, e);
+            None
+        }
+    }
+}
+
+// resource
+// the map this run is played on. None makes a board from GridConfig
+#[derive(Default)]
+pub struct LoadedMap(pub Option<MapFile>);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MapFile {
+    pub width: usize,
+    pub height: usize,
+    pub castle: Coords,
+    pub floor: Vec<Coords>,
+    pub mines: Vec<Coords>,
+    // walls the map says are empty
+    pub empty: Vec<Coords>,
+    // anything that isn't plain
+    pub terrain: Vec<(Coords, Terrain)>,
+    pub spawns: Vec<Coords>,
+}
+
+#[derive(Debug)]
+pub enum MapError {
+    Empty,
+    // rows have to be the same length
+    Ragged { line: usize },
+    UnknownTile { line: usize, tile: char },
+    NoCastle,
+    TooManyCastles,
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Empty => write!(f, "map has no rows"),
+            MapError::Ragged { line } => write!(f, "line {} is a different length", line),
+            MapError::UnknownTile { line, tile } => {
+                write!(f, "line {} has an unknown tile '{}'", line, tile)
+            }
+            MapError::NoCastle => write!(f, "map has no castle"),
+            MapError::TooManyCastles => write!(f, "map has more than one castle"),
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
+impl MapFile {
+    pub fn parse(text: &str) -> Result<MapFile, MapError> {
+        // line numbers are kept for the errors
+        let rows: Vec<(usize, Vec<char>)> = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with("//"))
+            .map(|(i, line)| (i, line.chars().collect()))
+            .collect();
+
+        let height = rows.len();
+        let width = rows.first().ok_or(MapError::Empty)?.1.len();
+
+        let mut castle = None;
+        let mut map = MapFile {
+            width,
+            height,
+            castle: Coords::new(0, 0),
+            floor: Vec::new(),
+            mines: Vec::new(),
+            empty: Vec::new(),
+            terrain: Vec::new(),
+            spawns: Vec::new(),
+        };
+
+        for (row, (line, tiles)) in rows.iter().enumerate() {
+            if tiles.len() != width {
+                return Err(MapError::Ragged { line: *line });
+            }
+            // the file is read top down, but y goes up
+            let y = (height - 1 - row) as i32;
+            for (x, &symbol) in tiles.iter().enumerate() {
+                let coords = Coords::new(x as i32, y);
+                let tile = MapTile::from_symbol(symbol).ok_or(MapError::UnknownTile {
+                    line: *line,
+                    tile: symbol,
+                })?;
+                match tile {
+                    MapTile::Wall => {}
+                    MapTile::Mine => map.mines.push(coords),
+                    MapTile::Empty => map.empty.push(coords),
+                    MapTile::Floor => map.floor.push(coords),
+                    MapTile::Water | MapTile::Rock | MapTile::HighGround | MapTile::Swamp => {
+                        map.terrain.push((coords, tile.terrain()))
+                    }
+                    MapTile::Spawn => map.spawns.push(coords),
+                    MapTile::Castle => {
+                        if castle.is_some() {
+                            return Err(MapError::TooManyCastles);
+                        }
+                        castle = Some(coords);
+                        map.floor.push(coords);
+                    }
+                }
+            }
+        }
+
+        map.castle = castle.ok_or(MapError::NoCastle)?;
+        Ok(map)
+    }
+}
+
+// a map one tile at a time, for the editor
+// same order as the grid
+pub struct MapTiles {
+    pub width: usize,
+    pub height: usize,
+    tiles: Vec<MapTile>,
+}
+
+impl MapTiles {
+    // all walls with the castle in the middle
+    pub fn blank(width: usize, height: usize) -> Self {
+        let mut tiles = MapTiles {
+            width,
+            height,
+            tiles: vec![MapTile::Wall; width * height],
+        };
+        tiles.set(
+            Coords::new((width / 2) as i32, (height / 2) as i32),
+            MapTile::Castle,
+        );
+        tiles
+    }
+
+    pub fn from_map(map: &MapFile) -> Self {
+        let mut tiles = MapTiles {
+            width: map.width,
+            height: map.height,
+            tiles: vec![MapTile::Wall; map.width * map.height],
+        };
+        let lists = [
+            (&map.mines, MapTile::Mine),
+            (&map.empty, MapTile::Empty),
+            (&map.floor, MapTile::Floor),
+            (&map.spawns, MapTile::Spawn),
+        ];
+        for (list, tile) in lists {
+            for &c in list.iter() {
+                tiles.set(c, tile);
+            }
+        }
+        for &(c, terrain) in map.terrain.iter() {
+            tiles.set(c, MapTile::from_terrain(terrain));
+        }
+        tiles.set(map.castle, MapTile::Castle);
+        tiles
+    }
+
+    fn index(&self, coords: Coords) -> Option<usize> {
+        if coords.x < 0 || coords.y < 0 {
+            return None;
+        }
+        let (x, y) = (coords.x as usize, coords.y as usize);
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        Some(x * self.height + y)
+    }
+
+    pub fn get(&self, coords: Coords) -> Option<MapTile> {
+        self.index(coords).map(|i| self.tiles[i])
+    }
+
+    // there's only one castle. Painting a new one turns the old one to floor
+    pub fn set(&mut self, coords: Coords, tile: MapTile) {
+        if let Some(i) = self.index(coords) {
+            if tile == MapTile::Castle {
+                for old in self.tiles.iter_mut() {
+                    if *old == MapTile::Castle {
+                        *old = MapTile::Floor;
+                    }
+                }
+            }
+            self.tiles[i] = tile;
+        }
+    }
+
+    // in the format parse reads
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        for y in (0..self.height).rev() {
+            for x in 0..self.width {
+                text.push(self.tiles[x * self.height + y].symbol());
+            }
+            text.push('\n');
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ragged_rows() {
+        let text = "// a comment\n###\n#C\n###\n";
+        assert!(matches!(
+            MapFile::parse(text),
+            Err(MapError::Ragged { line: 3 })
+        ));
+    }
+
+    #[test]
+    fn unknown_symbol() {
+        let text = "###\n#C?\n###\n";
+        assert!(matches!(
+            MapFile::parse(text),
+            Err(MapError::UnknownTile { line: 2, tile: '?' })
+        ));
+    }
+
+    #[test]
+    fn castles() {
+        assert!(matches!(MapFile::parse(""), Err(MapError::Empty)));
+        assert!(matches!(
+            MapFile::parse("###\n#.#\n###\n"),
+            Err(MapError::NoCastle)
+        ));
+        assert!(matches!(
+            MapFile::parse("###\nC.C\n###\n"),
+            Err(MapError::TooManyCastles)
+        ));
+    }
+
+    // the first row in the file is the top of the board
+    #[test]
+    fn top_row_is_highest_y() {
+        let map = MapFile::parse("S##\n#C#\n*##\n").unwrap();
+        assert_eq!((map.width, map.height), (3, 3));
+        assert_eq!(map.spawns, vec![Coords::new(0, 2)]);
+        assert_eq!(map.castle, Coords::new(1, 1));
+        assert_eq!(map.mines, vec![Coords::new(0, 0)]);
+    }
+
+    // what the editor saves loads back the same
+    #[test]
+    fn round_trip() {
+        let text = "// comment\nS#^H%\n-*.CX\n\n##-.S\n";
+        let map = MapFile::parse(text).unwrap();
+        let tiles = MapTiles::from_map(&map);
+        assert_eq!(tiles.get(Coords::new(3, 1)), Some(MapTile::Castle));
+        assert_eq!(tiles.get(Coords::new(0, 2)), Some(MapTile::Spawn));
+        let again = MapFile::parse(&tiles.to_text()).unwrap();
+        assert_eq!(again, map);
+    }
+}
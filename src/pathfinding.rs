@@ -1,317 +1,294 @@
-use bevy::prelude::*;
+use std::{cmp::Reverse, collections::BinaryHeap};
 
-// use crate::grid::{self, Coords};
+use crate::{board::Coords, graph::GridGraph};
 
-pub struct PathfindingPlugin;
+// enemies find their way with the flow field
+// one search each was too slow for a horde
+// this is for one tile to another, like the editor checking spawns
 
-impl Plugin for PathfindingPlugin {
-    fn build(&self, app: &mut App) {
-        app.add_startup_system(test_path);
-    }
-}
-
-// got from
-// https://www.youtube.com/watch?v=1bO1FdEThnU&ab_channel=CodeMonkey
-// converted to rust
-// Nodes
-// have G, H, F
+// A*
 // G: cost from the start node
-// H: Heurostoc cost to reach end
+// H: heuristic cost to reach the end. Never more than the real cost
 // F: G + H
-// open list - need to evaluate
-// closed list - already evaluated
-
-const STRAIGHT_COST: usize = 10;
-const DIAGONAL_COST: usize = 14;
+// the open set is a binary heap, lowest F comes out first
+// closed is a bit per tile
+
+// no tile came before this one
+const NONE: u32 = u32::MAX;
+
+// everything a search needs, kept between searches
+// once it has seen a graph, searching it again doesn't allocate
+#[derive(Default)]
+pub struct PathSearch {
+    // indexed by GridGraph::index
+    g: Vec<u32>,
+    came_from: Vec<u32>,
+    closed: Vec<u64>,
+    // (f, h, tile). Ties go to whichever is closer to the end
+    open: BinaryHeap<Reverse<(u32, u32, u32)>>,
+    path: Vec<Coords>,
+}
 
-#[derive(Copy, Clone)]
-struct PathNode {
-    x: usize,
-    y: usize,
+impl PathSearch {
+    fn prepare<G: GridGraph>(&mut self, graph: &G) {
+        // only bigger if the graph grew
+        let len = graph.index_len();
+        self.g.resize(len, u32::MAX);
+        self.came_from.resize(len, NONE);
+        self.closed.resize(len.div_ceil(64), 0);
+
+        self.g.fill(u32::MAX);
+        self.came_from.fill(NONE);
+        self.closed.fill(0);
+        self.open.clear();
+        self.path.clear();
+    }
 
-    index: usize,
+    fn is_closed(&self, i: u32) -> bool {
+        self.closed[i as usize / 64] & (1 << (i % 64)) != 0
+    }
 
-    g: usize,
-    h: usize,
-    f: usize,
+    fn close(&mut self, i: u32) {
+        self.closed[i as usize / 64] |= 1 << (i % 64);
+    }
 
-    is_walkable: bool,
+    // the cheapest way from start to end, both included
+    // None if there isn't one
+    pub fn find_path<G: GridGraph>(
+        &mut self,
+        graph: &G,
+        start: Coords,
+        end: Coords,
+    ) -> Option<&[Coords]> {
+        self.prepare(graph);
+        let start_index = graph.index(start)? as u32;
+        let end_index = graph.index(end)? as u32;
+
+        self.g[start_index as usize] = 0;
+        let h = graph.distance_cost(start, end);
+        self.open.push(Reverse((h, h, start_index)));
+
+        while let Some(Reverse((_, _, current))) = self.open.pop() {
+            if current == end_index {
+                break;
+            }
+            // a cheaper way here was already taken
+            if self.is_closed(current) {
+                continue;
+            }
+            self.close(current);
+
+            let current_coords = graph.coords(current as usize).unwrap();
+            let current_g = self.g[current as usize];
+            for n_coords in graph.neighbours(current_coords) {
+                let n = match graph.index(n_coords) {
+                    Some(n) => n as u32,
+                    None => continue,
+                };
+                if self.is_closed(n) {
+                    continue;
+                }
+                // check if node is walkable
+                let cost = match graph.cost(n_coords) {
+                    Some(cost) => cost,
+                    None => continue,
+                };
+
+                let tentative_g_cost = current_g + graph.step_cost(current_coords, n_coords) * cost;
+                if tentative_g_cost < self.g[n as usize] {
+                    self.came_from[n as usize] = current;
+                    self.g[n as usize] = tentative_g_cost;
+                    let h = graph.distance_cost(n_coords, end);
+                    self.open.push(Reverse((tentative_g_cost + h, h, n)));
+                }
+            }
+        }
 
-    came_from_index: Option<usize>,
-}
+        if start_index != end_index && self.came_from[end_index as usize] == NONE {
+            // didn't find a path
+            return None;
+        }
 
-impl PathNode {
-    fn calculate_f_cost(&mut self) {
-        if self.g > usize::MAX - self.h {
-            // too big
-            // don't add to max
-            self.f = usize::MAX;
-        } else {
-            self.f = self.g + self.h;
+        // walk back from the end, then flip it so it starts at the start
+        let mut current = end_index;
+        self.path.push(end);
+        while self.came_from[current as usize] != NONE {
+            current = self.came_from[current as usize];
+            self.path.push(graph.coords(current as usize).unwrap());
         }
+        self.path.reverse();
+        Some(&self.path)
     }
 }
 
-fn test_path() {
-    find_path(0, 0, 3, 1);
-    // Path is:
-    // (1, 3)
-    // (2, 1)
-    // (1, 1)
-    // (0, 0)
-    // should be
-    // (3, 1)
-    // (2, 1)
-    // (1, 0)
-    // (0, 0)
-    // using reverse indices,
-    // Path is:
-    // (3, 1)
-    // (2, 1)
-    // (1, 1)
-    // (0, 0)
-    // which is correct
-    // So why is his way correct for him?
-    // what is backwards in mine?
-    // do unity nativelists add to the front?
-    // maybe because he does array[index] = item
-    // which isn't the order the loops run in
-    // they are created down to up, left to right
-    // vec.push is down to up, left to right
-    // he inserts them left to right, down to up
+// one off search. Keep a PathSearch around to search more than once
+pub fn find_path<G: GridGraph>(graph: &G, start: Coords, end: Coords) -> Option<Vec<Coords>> {
+    PathSearch::default()
+        .find_path(graph, start, end)
+        .map(|path| path.to_vec())
 }
 
-pub fn find_path(start_x: usize, start_y: usize, end_x: usize, end_y: usize) {
-    let grid_width = 4;
-    let grid_height = 5;
-
-    let mut nodes = Vec::new();
-
-    for i in 0..grid_width {
-        for j in 0..grid_height {
-            let g = usize::MAX;
-            let h = calculate_dist_cost(i, j, end_x, end_y);
-            let index = calculate_index(i, j, grid_height);
-            let f = g; // g + h, but g is max value so don't want overflow
-            let node = PathNode {
-                x: i,
-                y: j,
-                index,
-                g,
-                h,
-                f,
-                is_walkable: true,
-                came_from_index: None,
-            };
-            nodes.push(node);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::{Board, NumberRule, Terrain, Topology},
+        flow_field::FlowField,
+    };
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    // walls, floor, towers, swamp and water all over
+    fn random_board(topology: Topology, width: usize, height: usize, seed: u64) -> Board {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut board = Board::new(width, height, topology, NumberRule::Adjacent);
+        for _ in 0..width * height / 3 {
+            let c = Coords::new(
+                rng.gen_range(0..width as i32),
+                rng.gen_range(0..height as i32),
+            );
+            match rng.gen_range(0..4) {
+                0 => {
+                    let _ = board.try_place_tower(c);
+                }
+                1 => {
+                    board.reveal(c);
+                }
+                2 => board.set_terrain(c, Terrain::Swamp),
+                _ => board.set_terrain(c, Terrain::Water),
+            }
         }
+        board
     }
 
-    let index = calculate_index(start_x, start_y, grid_height);
-    if let Some(mut start_node) = nodes.get_mut(index) {
-        start_node.g = 0;
-        start_node.calculate_f_cost();
-
-        let mut open_list: Vec<usize> = Vec::new();
-        let mut closed_list: Vec<usize> = Vec::new();
-
-        open_list.push(start_node.index);
-
-        let end_index = calculate_index(end_x, end_y, grid_height);
-
-        let mut open_list_len = open_list.len().clone();
-        let mut count = 0;
-        while open_list_len > 0 && count < 50 {
-            count += 1;
-            if let Some(current_node_index) = get_lowest_fcost_index(&open_list, &nodes) {
-                if current_node_index == end_index {
-                    // reached dest
-                    break;
-                }
-
-                let current_node = nodes.get(current_node_index);
+    // what walking the path costs, the same way A* and the flow field add it up
+    fn cost(board: &Board, path: &[Coords]) -> u32 {
+        path.windows(2)
+            .map(|step| board.step_cost(step[0], step[1]) * board.path_cost(step[1]).unwrap())
+            .sum()
+    }
 
-                if let Some(current_node) = current_node {
-                    let current_node = current_node.clone();
-                    // remove the current node from the open list
-                    for (i, item) in open_list.iter().enumerate() {
-                        if *item == current_node_index {
-                            open_list.remove(i);
-                            open_list_len = open_list.len().clone();
+    // follows the flow field to the destination
+    fn flow_cost(board: &Board, flow_field: &FlowField, start: Coords, end: Coords) -> Option<u32> {
+        let mut path = vec![start];
+        let mut current = start;
+        while current != end {
+            current = flow_field.next_tile(board, current)?;
+            path.push(current);
+        }
+        Some(cost(board, &path))
+    }
 
-                            break;
-                        }
+    // both find the cheapest way, so they have to agree on what it costs
+    // even if they pick different tiles on the way
+    #[test]
+    fn cost_matches_flow_field() {
+        let mut found = 0;
+        for topology in [Topology::Square, Topology::Hex] {
+            for seed in 0..5 {
+                let board = random_board(topology, 20, 16, seed);
+                let end = Coords::new(10, 8);
+                let flow_field = FlowField::new(&board, end);
+                let mut rng = StdRng::seed_from_u64(seed);
+                for _ in 0..20 {
+                    let start = Coords::new(rng.gen_range(0..20), rng.gen_range(0..16));
+                    if !board.is_walkable(start) || !board.is_walkable(end) {
+                        continue;
                     }
-
-                    closed_list.push(current_node.index);
-
-                    let neighbours = get_neighbour_indicies(
-                        current_node.x,
-                        current_node.y,
-                        grid_width,
-                        grid_height,
+                    let path = find_path(&board, start, end);
+                    assert_eq!(
+                        path.as_deref().map(|p| cost(&board, p)),
+                        flow_cost(&board, &flow_field, start, end),
+                        "{:?} {:?} to {:?}",
+                        topology,
+                        start,
+                        end
                     );
-
-                    // get neighbours
-                    for n in neighbours {
-                        if closed_list.contains(&n) {
-                            continue;
-                        }
-
-                        if let Some(neighbour_node) = nodes.get_mut(n) {
-                            // check if node is walkable
-                            if !neighbour_node.is_walkable {
-                                continue;
-                            }
-
-                            let tentative_g_cost = current_node.g
-                                + calculate_dist_cost(
-                                    current_node.x,
-                                    current_node.y,
-                                    neighbour_node.x,
-                                    neighbour_node.y,
-                                );
-                            if tentative_g_cost < neighbour_node.g {
-                                neighbour_node.came_from_index = Some(current_node_index);
-                                neighbour_node.g = tentative_g_cost;
-                                neighbour_node.calculate_f_cost();
-
-                                if !open_list.contains(&neighbour_node.index) {
-                                    open_list.push(neighbour_node.index);
-                                    open_list_len = open_list.len().clone();
-                                }
-                            }
+                    if let Some(path) = path {
+                        found += 1;
+                        assert_eq!((path[0], path[path.len() - 1]), (start, end));
+                        for step in path.windows(2) {
+                            assert!(board.neighbours(step[0]).any(|n| n == step[1]));
                         }
                     }
                 }
-            } else {
-                // couldn't find a lowest fcost in the open set
-                // shouldn't be possible
-            }
-        }
-
-        if let Some(end_node) = nodes.get(end_index) {
-            if end_node.came_from_index.is_none() {
-                // didn't find a path
-                println!("Didn't find a path");
-            } else {
-                // did find a path
-                let path = calculate_path(&nodes, end_node);
-                println!("Path is: ");
-                for p in path {
-                    println!("({:?}, {:?})", p.x, p.y);
-                }
             }
         }
+        assert!(found > 100, "only {} paths found", found);
     }
-}
 
-// #[derive(Debug)]
-struct PathPoints {
-    x: usize,
-    y: usize,
-}
-
-fn calculate_path(nodes: &Vec<PathNode>, end_node: &PathNode) -> Vec<PathPoints> {
-    let mut v = Vec::new();
-
-    if let Some(_came_from) = end_node.came_from_index {
-        // do I need this base case?
-        // doesn't the loop handle it?
-        v.push(PathPoints {
-            x: end_node.x,
-            y: end_node.y,
-        });
+    // towers down the middle with water at the top leave one gap
+    #[test]
+    fn goes_around_towers_and_water() {
+        let mut board = Board::new(5, 5, Topology::Square, NumberRule::Adjacent);
+        for y in 0..3 {
+            board.try_place_tower(Coords::new(2, y)).unwrap();
+        }
+        board.set_terrain(Coords::new(2, 3), Terrain::Water);
 
-        let mut current_node = end_node;
-        while current_node.came_from_index.is_some() {
-            if let Some(came_from_index) = current_node.came_from_index {
-                if let Some(came_from_node) = nodes.get(came_from_index) {
-                    v.push(PathPoints {
-                        x: came_from_node.x,
-                        y: came_from_node.y,
-                    });
-                    current_node = came_from_node;
-                }
-            }
+        let path = find_path(&board, Coords::new(0, 0), Coords::new(4, 0)).unwrap();
+        assert!(path.contains(&Coords::new(2, 4)));
+        for &c in path.iter() {
+            assert!(board.is_walkable(c), "walked on {:?}", c);
         }
     }
 
-    v
-}
-
-fn get_neighbour_indicies(x: usize, y: usize, width: usize, height: usize) -> Vec<usize> {
-    let mut v = Vec::new();
-    let h = height - 1;
-    let w = width - 1;
-    if y < h {
-        v.push(calculate_index(x, y + 1, height)); // up
-    }
-    if y < h && x < w {
-        v.push(calculate_index(x + 1, y + 1, height)); // up right
-    }
-    if x < w {
-        v.push(calculate_index(x + 1, y, height)); // right
-    }
-    if y > 0 {
-        if x < w {
-            v.push(calculate_index(x + 1, y - 1, height)); // down right
+    #[test]
+    fn unreachable() {
+        let mut board = Board::new(5, 5, Topology::Square, NumberRule::Adjacent);
+        for y in 0..5 {
+            board.try_place_tower(Coords::new(2, y)).unwrap();
         }
-        v.push(calculate_index(x, y - 1, height)); // down
+        assert_eq!(
+            find_path(&board, Coords::new(0, 0), Coords::new(4, 0)),
+            None
+        );
+        // off the board
+        assert_eq!(
+            find_path(&board, Coords::new(0, 0), Coords::new(9, 9)),
+            None
+        );
     }
-    if y > 0 && x > 0 {
-        v.push(calculate_index(x - 1, y - 1, height)); // down left
+
+    #[test]
+    fn start_is_end() {
+        let board = Board::new(5, 5, Topology::Square, NumberRule::Adjacent);
+        let c = Coords::new(3, 1);
+        assert_eq!(find_path(&board, c, c), Some(vec![c]));
     }
-    if x > 0 {
-        v.push(calculate_index(x - 1, y, height)); // left
-        if y < h {
-            v.push(calculate_index(x - 1, y + 1, height)); // up left
+
+    // nothing left over from a bigger or different graph changes the answer
+    #[test]
+    fn reused_search_matches_fresh() {
+        let sizes = [
+            (Topology::Square, 20, 16),
+            (Topology::Hex, 8, 6),
+            (Topology::Square, 30, 24),
+            (Topology::Hex, 20, 16),
+            (Topology::Square, 6, 6),
+        ];
+        let boards: Vec<Board> = sizes
+            .iter()
+            .enumerate()
+            .map(|(seed, &(topology, width, height))| {
+                random_board(topology, width, height, seed as u64)
+            })
+            .collect();
+        let mut search = PathSearch::default();
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..3 {
+            for (board, &(_, width, height)) in boards.iter().zip(sizes.iter()) {
+                for _ in 0..10 {
+                    let mut random = || {
+                        Coords::new(
+                            rng.gen_range(0..width as i32),
+                            rng.gen_range(0..height as i32),
+                        )
+                    };
+                    let (start, end) = (random(), random());
+                    let reused = search.find_path(board, start, end).map(|p| p.to_vec());
+                    assert_eq!(reused, find_path(board, start, end));
+                }
+            }
         }
     }
-
-    v
-}
-
-fn get_lowest_fcost_index(open_list: &Vec<usize>, nodes: &Vec<PathNode>) -> Option<usize> {
-    open_list
-        .iter()
-        .min_by_key(|x| nodes.get(**x).unwrap().f)
-        .map(|x| *x)
-}
-
-fn calculate_index(x: usize, y: usize, grid_height: usize) -> usize {
-    //this gives
-    // 12 13 14 15
-    // 8 9 10 11
-    // 4 5 6 7
-    // 0 1 2 3
-    // x + y * grid_width
-    // this gives
-    // 3 7 11 15
-    // 2 6 10 14
-    // 1 5 9 13
-    // 0 4 8 12
-    // this is the order elements are created if
-    // for x { for y { }}
-    y + x * grid_height
-}
-
-fn calculate_dist_cost(a_x: usize, a_y: usize, b_x: usize, b_y: usize) -> usize {
-    // let x_dist = if a_x > b_x { a_x - b_x } else { b_x - a_x };
-    // let y_dist = if a_y > b_y { a_y - b_y } else { b_y - a_y };
-    // let remainder = if x_dist > y_dist {
-    //     x_dist - y_dist
-    // } else {
-    //     y_dist - x_dist
-    // };
-
-    // (0,1) -> (3,1)
-    // 3, 0
-    // r = 3
-    // 0*14 + 3*10 = 30
-
-    let x_dist = a_x.abs_diff(b_x);
-    let y_dist = a_y.abs_diff(b_y);
-    let remainder = x_dist.abs_diff(y_dist);
-    DIAGONAL_COST * x_dist.min(y_dist) + STRAIGHT_COST * remainder
 }
@@ -0,0 +1,396 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::board::{Board, Coords, TileState};
+
+// Works out which walls have to be mines (or can't be) using only
+// the numbers the player can see.
+// Every wall a number counts is a cell. Every number says how many
+// mines its cells hold. A cell can hold 0 mines, 1, or 2 with double mines.
+// A cell is certain if it's the same in every way
+// of filling in the mines that agrees with all the numbers.
+
+// how many cells a single search is allowed to try before giving up
+// giving up just means "not certain", never a wrong answer
+const SEARCH_LIMIT: usize = 20_000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Deduction {
+    Mine,
+    Safe,
+}
+
+struct Constraint {
+    cells: Vec<usize>,
+    target: usize,
+}
+
+// the walls numbers count and what the numbers say about them
+struct Frontier {
+    cells: Vec<Coords>,
+    constraints: Vec<Constraint>,
+    // most mines one cell can hold
+    max: usize,
+    // fewest and most mines each cell can hold
+    // narrowed by what earlier passes worked out
+    bounds: Vec<(usize, usize)>,
+}
+
+impl Frontier {
+    fn new(board: &Board, known: &HashMap<Coords, Deduction>) -> Self {
+        let mut cells = Vec::new();
+        let mut lookup: HashMap<Coords, usize> = HashMap::new();
+        let mut constraints = Vec::new();
+
+        for (coords, tile) in board.iter() {
+            if tile.state != TileState::Floor || !tile.clue {
+                continue;
+            }
+            let mut constraint_cells = Vec::new();
+            for n in board.number_area(coords) {
+                let is_wall = matches!(board.state(n), Some(s) if s.is_wall());
+                if !is_wall {
+                    continue;
+                }
+                let index = *lookup.entry(n).or_insert_with(|| {
+                    cells.push(n);
+                    cells.len() - 1
+                });
+                constraint_cells.push(index);
+            }
+            if !constraint_cells.is_empty() {
+                constraints.push(Constraint {
+                    cells: constraint_cells,
+                    // towers on mines are already known
+                    // a misplaced tower is shown, so it isn't one
+                    target: tile.number - tile.found,
+                });
+            }
+        }
+
+        let max = board.max_mines();
+        let bounds = cells
+            .iter()
+            .map(|c| match known.get(c) {
+                Some(Deduction::Mine) => (1, max),
+                Some(Deduction::Safe) => (0, 0),
+                None => (0, max),
+            })
+            .collect();
+        Frontier {
+            cells,
+            constraints,
+            max,
+            bounds,
+        }
+    }
+
+    // which numbers each cell is part of
+    fn cell_constraints(&self) -> Vec<Vec<usize>> {
+        let mut cell_constraints = vec![Vec::new(); self.cells.len()];
+        for (ci, constraint) in self.constraints.iter().enumerate() {
+            for &cell in constraint.cells.iter() {
+                cell_constraints[cell].push(ci);
+            }
+        }
+        cell_constraints
+    }
+
+    // cells that share a number have to be solved together
+    // cells that don't can be solved on their own, which keeps the searches small
+    // each group is walked out from its first cell so neighbours are tried one after another
+    // and a bad guess gets caught by the next number instead of 50 cells later
+    fn components(&self, cell_constraints: &[Vec<usize>]) -> Vec<Vec<usize>> {
+        let mut seen = vec![false; self.cells.len()];
+        let mut groups = Vec::new();
+
+        for first in 0..self.cells.len() {
+            if seen[first] {
+                continue;
+            }
+            seen[first] = true;
+            let mut group = vec![first];
+            let mut next = 0;
+            while next < group.len() {
+                let cell = group[next];
+                next += 1;
+                for &ci in cell_constraints[cell].iter() {
+                    for &other in self.constraints[ci].cells.iter() {
+                        if !seen[other] {
+                            seen[other] = true;
+                            group.push(other);
+                        }
+                    }
+                }
+            }
+            groups.push(group);
+        }
+        groups
+    }
+}
+
+enum Outcome {
+    Found(Vec<usize>),
+    Impossible,
+    GaveUp,
+}
+
+// backtracking over one group of cells
+struct Search<'a> {
+    frontier: &'a Frontier,
+    cell_constraints: &'a [Vec<usize>],
+    cells: &'a [usize],
+    // indexed by frontier cell. how many mines it holds
+    assignment: Vec<Option<usize>>,
+    steps: usize,
+}
+
+impl<'a> Search<'a> {
+    fn new(frontier: &'a Frontier, cell_constraints: &'a [Vec<usize>], cells: &'a [usize]) -> Self {
+        Search {
+            frontier,
+            cell_constraints,
+            cells,
+            assignment: vec![None; frontier.cells.len()],
+            steps: 0,
+        }
+    }
+
+    fn run(&mut self, fixed: Option<(usize, usize)>) -> Outcome {
+        for &cell in self.cells.iter() {
+            self.assignment[cell] = None;
+        }
+        self.steps = 0;
+        if let Some((cell, mines)) = fixed {
+            let (lo, hi) = self.frontier.bounds[cell];
+            if mines < lo || mines > hi {
+                return Outcome::Impossible;
+            }
+            self.assignment[cell] = Some(mines);
+            if !self.still_possible(cell) {
+                return Outcome::Impossible;
+            }
+        }
+
+        match self.step(0) {
+            Some(true) => Outcome::Found(
+                self.cells
+                    .iter()
+                    .map(|&c| self.assignment[c].unwrap_or(0))
+                    .collect(),
+            ),
+            Some(false) => Outcome::Impossible,
+            None => Outcome::GaveUp,
+        }
+    }
+
+    // Some(true) found one, Some(false) there isn't one, None ran out of steps
+    fn step(&mut self, next: usize) -> Option<bool> {
+        self.steps += 1;
+        if self.steps > SEARCH_LIMIT {
+            return None;
+        }
+
+        let cell = match self.cells.get(next) {
+            Some(&cell) => cell,
+            None => return Some(true),
+        };
+        if self.assignment[cell].is_some() {
+            return self.step(next + 1);
+        }
+
+        let (lo, hi) = self.frontier.bounds[cell];
+        for mines in lo..=hi {
+            self.assignment[cell] = Some(mines);
+            if self.still_possible(cell) {
+                match self.step(next + 1) {
+                    Some(false) => {}
+                    other => return other,
+                }
+            }
+        }
+        self.assignment[cell] = None;
+        Some(false)
+    }
+
+    // can every number this cell touches still be met?
+    fn still_possible(&self, cell: usize) -> bool {
+        for &ci in self.cell_constraints[cell].iter() {
+            let constraint = &self.frontier.constraints[ci];
+            let mut mines = 0;
+            let mut unknown = 0;
+            for &c in constraint.cells.iter() {
+                match self.assignment[c] {
+                    Some(m) => mines += m,
+                    None => unknown += 1,
+                }
+            }
+            if mines > constraint.target || mines + unknown * self.frontier.max < constraint.target
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+// every wall the visible numbers prove is a mine or safe
+// each pass starts from what the last one proved, until nothing new turns up
+// a search that gave up on a big group can finish once part of it is known
+pub fn deduce(board: &Board) -> Vec<(Coords, Deduction)> {
+    let mut known = HashMap::new();
+    let mut deductions = Vec::new();
+    loop {
+        let found = deduce_pass(board, &known);
+        if found.is_empty() {
+            return deductions;
+        }
+        for &(coords, deduction) in found.iter() {
+            known.insert(coords, deduction);
+        }
+        deductions.extend(found);
+    }
+}
+
+// the walls not in known that this pass can prove
+fn deduce_pass(board: &Board, known: &HashMap<Coords, Deduction>) -> Vec<(Coords, Deduction)> {
+    let frontier = Frontier::new(board, known);
+    let cell_constraints = frontier.cell_constraints();
+    let mut deductions = Vec::new();
+    // [can hold 0, can hold 1, can hold 2]
+    let mut possible = vec![[false; 3]; frontier.cells.len()];
+
+    for cells in frontier.components(&cell_constraints) {
+        let mut search = Search::new(&frontier, &cell_constraints, &cells);
+
+        let mark = |possible: &mut Vec<[bool; 3]>, solution: &[usize]| {
+            for (&cell, &mines) in cells.iter().zip(solution.iter()) {
+                possible[cell][mines] = true;
+            }
+        };
+
+        match search.run(None) {
+            Outcome::Found(solution) => mark(&mut possible, &solution),
+            // the numbers contradict each other or it's too big to check
+            // nothing here is certain
+            Outcome::Impossible | Outcome::GaveUp => continue,
+        }
+
+        for &cell in cells.iter() {
+            if known.contains_key(&frontier.cells[cell]) {
+                continue;
+            }
+            for mines in 0..=frontier.max {
+                if possible[cell][mines] {
+                    continue;
+                }
+                match search.run(Some((cell, mines))) {
+                    Outcome::Found(solution) => mark(&mut possible, &solution),
+                    Outcome::Impossible => {}
+                    // didn't finish. treat it as possible
+                    Outcome::GaveUp => possible[cell][mines] = true,
+                }
+            }
+
+            // a mine is a mine. Telling 1 from 2 doesn't matter for building
+            let can_be_mine = possible[cell][1..].iter().any(|&p| p);
+            match (possible[cell][0], can_be_mine) {
+                (true, false) => deductions.push((frontier.cells[cell], Deduction::Safe)),
+                (false, true) => deductions.push((frontier.cells[cell], Deduction::Mine)),
+                _ => {}
+            }
+        }
+    }
+
+    deductions
+}
+
+// mines a number counts that the numbers can't prove
+// sorted so no guess mode takes them out in the same order every run
+pub fn unproven_mines(board: &Board) -> Vec<Coords> {
+    let proven: HashSet<Coords> = deduce(board)
+        .into_iter()
+        .filter(|&(_, deduction)| deduction == Deduction::Mine)
+        .map(|(coords, _)| coords)
+        .collect();
+
+    let mut unproven = HashSet::new();
+    for (coords, tile) in board.iter() {
+        if tile.state != TileState::Floor || !tile.clue {
+            continue;
+        }
+        for n in board.number_area(coords) {
+            let mine_wall = matches!(board.get(n), Some(t) if t.mine && t.state.is_wall());
+            if mine_wall && !proven.contains(&n) {
+                unproven.insert(n);
+            }
+        }
+    }
+    let mut unproven: Vec<Coords> = unproven.into_iter().collect();
+    unproven.sort();
+    unproven
+}
+
+// every number can be filled without guessing
+pub fn is_solvable(board: &Board) -> bool {
+    unproven_mines(board).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forced_mine() {
+        let board = Board::from_rows(&["C.*"]);
+        assert_eq!(deduce(&board), vec![(Coords::new(2, 0), Deduction::Mine)]);
+        assert!(is_solvable(&board));
+    }
+
+    // a 1 next to a 1 that sees one less wall
+    // the extra wall can't be the mine
+    #[test]
+    fn forced_safe() {
+        let board = Board::from_rows(&["#*#", "C.."]);
+        assert_eq!(deduce(&board), vec![(Coords::new(0, 1), Deduction::Safe)]);
+        // safe isn't enough, there's no number to fill
+        assert!(!is_solvable(&board));
+    }
+
+    #[test]
+    fn fifty_fifty() {
+        let board = Board::from_rows(&["*#", "C."]);
+        assert!(deduce(&board).is_empty());
+        assert!(!is_solvable(&board));
+    }
+
+    // one number being fillable isn't enough
+    #[test]
+    fn a_guess_elsewhere_isnt_solvable() {
+        let board = Board::from_rows(&["*##.*", "C.#.."]);
+        let deductions = deduce(&board);
+        assert!(deductions.contains(&(Coords::new(4, 1), Deduction::Mine)));
+        assert!(!deductions.iter().any(|&(c, _)| c == Coords::new(0, 1)));
+        assert_eq!(unproven_mines(&board), vec![Coords::new(0, 1)]);
+        assert!(!is_solvable(&board));
+    }
+
+    // the 2 on the right only sees two walls, so both are mines
+    // and that's all the other 2 needs, so its corner is safe
+    #[test]
+    fn every_mine_proved() {
+        let board = Board::from_rows(&["#**", "C.."]);
+        let deductions = deduce(&board);
+        assert!(deductions.contains(&(Coords::new(0, 1), Deduction::Safe)));
+        assert!(is_solvable(&board));
+    }
+
+    // a tower on the mine leaves nothing to find
+    #[test]
+    fn filled_number_is_solvable() {
+        let mut board = Board::from_rows(&["C.*"]);
+        board.try_place_tower(Coords::new(2, 0)).unwrap();
+        board.count_tower(Coords::new(2, 0));
+        assert!(deduce(&board).is_empty());
+        assert!(is_solvable(&board));
+    }
+}
@@ -1,7 +1,8 @@
 use crate::{
+    board::Board,
     castle::Castle,
     enemy::Enemy,
-    grid::{clear_selection, ClearSelectionsEvent, Grid, Selection, Tile, TileState},
+    grid::{clear_selection, ClearSelectionsEvent, Grid, Selection, Tile},
     loading::SpriteAssets,
     ui::{update_buttons, ButtonPressEvent},
     utility, GameState,
@@ -27,7 +28,6 @@ impl Plugin for TowerPlugin {
         app.add_system_set(
             SystemSet::on_update(GameState::Playing)
                 .with_system(spawn_tower.after(update_buttons).before(clear_selection))
-                .with_system(update_tower_position.before(tower_tick))
                 .with_system(tower_tick)
                 .with_system(move_bullets)
                 .with_system(bullet_collision.after(move_bullets))
@@ -53,8 +53,8 @@ impl Plugin for TowerPlugin {
 }
 
 pub struct TowerPlacedEvent {
-    pub x: usize,
-    pub y: usize,
+    pub x: i32,
+    pub y: i32,
     // type of tower?
 }
 
@@ -538,32 +538,21 @@ pub fn setup_towers(mut tower_server: ResMut<TowerServer>, textures: Res<SpriteA
 fn spawn_tower(
     mut commands: Commands,
     mut ev_button_press: EventReader<ButtonPressEvent>,
-    mut q_selection: Query<(Entity, &mut Tile), With<Selection>>,
-    grid: Res<Grid>,
-    q_tiles: Query<&Tile, Without<Selection>>,
+    q_selection: Query<(Entity, &Tile), With<Selection>>,
+    mut board: ResMut<Board>,
     mut ev_clear_selection: EventWriter<ClearSelectionsEvent>,
     mut ev_tower_placed: EventWriter<TowerPlacedEvent>,
     tower_server: Res<TowerServer>,
     mut q_castle: Query<&mut Castle>,
+    grid: Res<Grid>,
 ) {
     for mut castle in q_castle.iter_mut() {
         for ev in ev_button_press.iter() {
             ev_clear_selection.send(ClearSelectionsEvent);
             let tower = tower_server.towers.get(ev.button_number).unwrap();
             //for tower in tower_server.towers.iter() {
-            for (ent, mut tile) in q_selection.iter_mut() {
-                let mut floor_nearby = false;
-                for neighbour in grid.get_ring(tile.x, tile.y, 1).into_iter().flatten() {
-                    // if let Some(info) = neighbour {
-                    if let Ok(tile) = q_tiles.get(neighbour.entity) {
-                        if tile.tile_state == TileState::Floor {
-                            floor_nearby = true;
-                            break;
-                        }
-                    }
-                    // }
-                }
-                if !floor_nearby {
+            for (ent, tile) in q_selection.iter() {
+                if !board.can_build(tile.coords()) {
                     println!("Tower failed. No floor nearby {}, {}", tile.x, tile.y);
                     continue;
                 }
@@ -573,11 +562,15 @@ fn spawn_tower(
                     continue;
                 }
 
-                let result = tile.try_spawn_tower();
+                let result = board.try_place_tower(tile.coords());
                 match result {
                     Ok(_) => {
                         // money -= tower.cost;
                         castle.money -= tower.cost;
+                        // high ground sees further, swamp less
+                        let mut tower = tower.clone();
+                        tower.range *= board.terrain(tile.coords()).range_bonus();
+                        tower.set_position(grid.tile_to_world(tile.coords()));
                         let child = commands
                             .spawn_bundle(SpriteBundle {
                                 texture: tower.visuals.texture.clone(),
@@ -627,12 +620,6 @@ fn spawn_tower(
     }
 }
 
-fn update_tower_position(mut q_towers: Query<(&mut Tower, &GlobalTransform), Added<Tower>>) {
-    for (mut tower, trans) in q_towers.iter_mut() {
-        tower.set_position(trans.translation().truncate());
-    }
-}
-
 #[derive(Component)]
 struct TowerRangeIndicator;
 
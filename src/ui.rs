@@ -1,9 +1,12 @@
 use bevy::prelude::*;
 
 use crate::{
-    castle::Castle,
+    board::Contradiction,
+    castle::{Castle, TerritoryInfo},
     director::{EndScreenEvent, SpawnInfo},
+    grid::{GridConfig, MineRng, NumberContradictionEvent},
     loading::{FontAssets, SpriteAssets},
+    map::LoadedMap,
     tower::TowerServer,
     GameState,
 };
@@ -20,14 +23,16 @@ impl Plugin for UiPlugin {
             .add_system_set(
                 SystemSet::on_update(GameState::MainMenu)
                     .with_system(click_play_button)
-                    .with_system(switch_to_playing),
+                    .with_system(switch_to_playing)
+                    .with_system(update_menu_options),
             )
             .add_system_set(SystemSet::on_exit(GameState::MainMenu).with_system(cleanup_menu));
         //spawn_director_menu
         app.add_system_set(
             SystemSet::on_enter(GameState::Playing)
                 .with_system(spawn_tower_menu)
-                .with_system(spawn_director_menu),
+                .with_system(spawn_director_menu)
+                .with_system(spawn_contradiction_text),
         )
         .add_system_set(
             SystemSet::on_update(GameState::Playing)
@@ -35,6 +40,7 @@ impl Plugin for UiPlugin {
                 .with_system(update_castle_stats)
                 .with_system(update_tower_info_panel)
                 .with_system(update_director_panel)
+                .with_system(show_contradiction)
                 .with_system(setup_end_menu),
         );
 
@@ -72,6 +78,10 @@ pub struct ButtonInfo {
 #[derive(Component)]
 pub struct StartButton;
 
+// the hotkeys on the main menu and what each one is set to
+#[derive(Component)]
+pub struct MenuOptionsText;
+
 fn setup_main_menu(
     mut commands: Commands,
     font_assets: Res<FontAssets>,
@@ -120,6 +130,66 @@ fn setup_main_menu(
                 ..default()
             });
         });
+
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text::from_section(
+                String::new(),
+                TextStyle {
+                    font: font_assets.fira_sans.clone(),
+                    font_size: 20.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            ),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(20.0),
+                    top: Val::Px(20.0),
+                    ..default()
+                },
+                ..default()
+            },
+            ..default()
+        })
+        .insert(MenuOptionsText)
+        .insert(StartButton);
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+// rewritten whenever a hotkey changes something
+// the text starts empty so the first frame fills it in too
+fn update_menu_options(
+    config: Res<GridConfig>,
+    territory_info: Res<TerritoryInfo>,
+    loaded: Res<LoadedMap>,
+    mut q_text: Query<&mut Text, With<MenuOptionsText>>,
+) {
+    for mut text in q_text.iter_mut() {
+        let changed = config.is_changed() || territory_info.is_changed() || loaded.is_changed();
+        if !changed && !text.sections[0].value.is_empty() {
+            continue;
+        }
+        text.sections[0].value = [
+            format!("T  Topology: {:?}", config.topology),
+            format!("N  Numbers: {:?}", config.number_rule),
+            format!("L  Terrain: {}", on_off(config.terrain)),
+            format!("I  Endless: {}", on_off(config.endless)),
+            format!("M  Saved map: {}", on_off(loaded.0.is_some())),
+            format!("G  No guess: {}", on_off(territory_info.no_guess)),
+            format!("E  Expansion: {:?}", territory_info.expansion),
+            format!("R  Ring shape: {:?}", territory_info.shape),
+            "Tab  Map editor".to_string(),
+        ]
+        .join("\n");
+    }
 }
 
 fn click_play_button(
@@ -576,6 +646,7 @@ fn spawn_castle_stats(
     fonts: &Res<FontAssets>,
     health: u32,
     money: u32,
+    seed: u64,
 ) {
     // let font = asset_server.load("fonts/FiraSans-Bold.ttf");
     let font = fonts.fira_sans.clone();
@@ -609,11 +680,28 @@ fn spawn_castle_stats(
                 TextSection::new(
                     format!("{:}\n", money),
                     TextStyle {
-                        font,
+                        font: font.clone(),
                         font_size: 25.0,
                         color: Color::GOLD,
                     },
                 ),
+                // so a board can be shared or played again
+                TextSection::new(
+                    "Seed: ",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 15.0,
+                        color: Color::WHITE,
+                    },
+                ),
+                TextSection::new(
+                    format!("{:}\n", seed),
+                    TextStyle {
+                        font,
+                        font_size: 15.0,
+                        color: Color::GOLD,
+                    },
+                ),
             ])
             .with_text_alignment(TextAlignment::TOP_RIGHT)
             .with_style(Style {
@@ -663,11 +751,18 @@ fn update_castle_stats(
     fonts: Res<FontAssets>,
     q_castle: Query<&Castle, Changed<Castle>>,
     mut q_castle_ui: Query<&mut Text, With<CastleUi>>,
+    mine_rng: Res<MineRng>,
 ) {
     for castle in q_castle.iter() {
         //println!("Updating stats");
         if q_castle_ui.is_empty() {
-            spawn_castle_stats(&mut commands, &fonts, castle.health, castle.money);
+            spawn_castle_stats(
+                &mut commands,
+                &fonts,
+                castle.health,
+                castle.money,
+                mine_rng.seed,
+            );
         }
         for mut text in q_castle_ui.iter_mut() {
             text.sections[1].value = format!("{:}\n", castle.health);
@@ -676,6 +771,68 @@ fn update_castle_stats(
     }
 }
 
+// how long the message about a wrong number stays up
+const CONTRADICTION_SECONDS: f32 = 3.0;
+
+#[derive(Component)]
+struct ContradictionText {
+    timer: Timer,
+}
+
+fn spawn_contradiction_text(mut commands: Commands, fonts: Res<FontAssets>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: fonts.fira_sans.clone(),
+                    font_size: 25.0,
+                    // #e43b44 same as the number
+                    color: Color::rgb_u8(0xe4, 0x3b, 0x44),
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    bottom: Val::Px(5.0),
+                    left: Val::Percent(30.0),
+                    ..default()
+                },
+                ..default()
+            }),
+        )
+        .insert(ContradictionText {
+            timer: Timer::from_seconds(CONTRADICTION_SECONDS, false),
+        });
+}
+
+// the number turns red on the board
+// this says why
+fn show_contradiction(
+    mut ev_contradiction: EventReader<NumberContradictionEvent>,
+    mut q_text: Query<(&mut Text, &mut ContradictionText)>,
+    time: Res<Time>,
+) {
+    let latest = ev_contradiction.iter().last();
+    for (mut text, mut flash) in q_text.iter_mut() {
+        if let Some(ev) = latest {
+            text.sections[0].value = match ev.contradiction {
+                Contradiction::TooManyTowers => {
+                    format!("Too many towers next to the number at {}, {}", ev.x, ev.y)
+                }
+                Contradiction::NotEnoughWalls => {
+                    format!("Not enough walls left for the number at {}, {}", ev.x, ev.y)
+                }
+            };
+            flash.timer.reset();
+        }
+        flash.timer.tick(time.delta());
+        if flash.timer.just_finished() {
+            text.sections[0].value.clear();
+        }
+    }
+}
+
 #[derive(Component)]
 struct EndButton;
 